@@ -1,8 +1,11 @@
 use std::any::type_name;
 
-use bd_suffix::gens::{
-    builders::*,
-    modes::{IndexMode, StrIndex},
+use bd_suffix::{
+    gens::{
+        builders::*,
+        modes::{IndexMode, StrIndex},
+    },
+    SuffixArray,
 };
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
@@ -30,23 +33,41 @@ fn create_bench_long_sais(c: &mut Criterion) {
     str_group.finish();
 }
 
-fn full_set(
-    group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>,
-    target: &'static str,
-    label: &'static str,
-) {
-    bench_inner::<NaiveBuilder, _>(group, target, (), label);
-    bench_inner::<NaiveBuilder, _>(group, target, StrIndex, label);
-    bench_inner::<BucketBuilder, _>(group, target, (), label);
-    bench_inner::<BucketBuilder, _>(group, target, StrIndex, label);
-    bench_inner::<TwoStageBuilder, _>(group, target, (), label);
-    bench_inner::<TwoStageBuilder, _>(group, target, StrIndex, label);
-    bench_inner::<TwoStageBuilderU8, _>(group, target, (), label);
-    bench_inner::<TwoStageBuilderU8, _>(group, target, StrIndex, label);
-    bench_inner::<SAISBuilder, _>(group, target, (), label);
-    bench_inner::<SAISBuilder, _>(group, target, StrIndex, label);
-    bench_inner::<SAISBuilderU8, _>(group, target, (), label);
-    bench_inner::<SAISBuilderU8, _>(group, target, StrIndex, label);
+fn create_bench_merge(c: &mut Criterion) {
+    let mut merge_group = c.benchmark_group("merge");
+    let mut seed = 88172645463325252u64;
+    let random = (0..1_000_000)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            ['A', 'C', 'G', 'T'][(seed >> 40) as usize % 4]
+        })
+        .collect::<String>();
+    for (target, label) in [
+        ("abcab".repeat(40000), "200000-periodic"),
+        (random, "1000000-random"),
+    ] {
+        let (left, right) = target.split_at(target.len() / 2);
+        let left = SAISBuilderU8::build(left, StrIndex);
+        let right = SAISBuilderU8::build(right, StrIndex);
+        merge_group.bench_function(BenchmarkId::new("merge", label), |b| {
+            b.iter(|| {
+                black_box(SuffixArray::merge(target.as_str(), &left, &right));
+            })
+        });
+        merge_group.bench_function(BenchmarkId::new("SAISBuilder", label), |b| {
+            b.iter(|| {
+                black_box(SAISBuilder::build(target.as_str(), StrIndex));
+            })
+        });
+        merge_group.bench_function(BenchmarkId::new("SAISBuilderU8", label), |b| {
+            b.iter(|| {
+                black_box(SAISBuilderU8::build(target.as_str(), StrIndex));
+            })
+        });
+    }
+    merge_group.finish();
 }
 
 fn unit_set(
@@ -126,6 +147,7 @@ criterion_group!(
     benches,
     create_bench_unit,
     create_bench_str,
-    create_bench_long_sais
+    create_bench_long_sais,
+    create_bench_merge
 );
 criterion_main!(benches);
@@ -0,0 +1,173 @@
+//! Appendable suffix array for growing values.
+//!
+//! [`AppendableSuffixArray`] keeps the values in segments, each with the suffix array of its own values,
+//! like a log-structured merge tree. An append builds a new segment, and the last segments are
+//! rebuilt as one while the earlier is not larger than twice the later, so each value is rebuilt
+//! `O(log n)` times in total.
+//!
+//! The suffixes of a segment are sorted only up to the end of the segment, so a search checks the
+//! positions near the end of each segment directly against the whole values.
+
+use crate::{
+    gens::{builders::SAISBuilder, IndexMode},
+    SuffixArray,
+};
+
+/// Segment of the values with its suffix array.
+#[derive(Debug, Clone)]
+struct Segment {
+    start: usize,
+    end: usize,
+    // positions relative to `start`
+    indices: Vec<usize>,
+}
+
+/// Suffix array supporting [`append`](`AppendableSuffixArray::append`) without a full rebuild.
+///
+/// The mode is called with the positions relative to the segment.
+#[derive(Debug, Clone)]
+pub struct AppendableSuffixArray<T, M = ()> {
+    values: Vec<T>,
+    mode: M,
+    segments: Vec<Segment>,
+}
+
+impl<T, M> Default for AppendableSuffixArray<T, M>
+where
+    T: Ord + Clone,
+    M: IndexMode<T> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new(M::default())
+    }
+}
+
+impl<T, M> AppendableSuffixArray<T, M>
+where
+    T: Ord + Clone,
+    M: IndexMode<T> + Clone,
+{
+    /// Create an empty array.
+    pub fn new(mode: M) -> Self {
+        Self {
+            values: vec![],
+            mode,
+            segments: vec![],
+        }
+    }
+
+    /// Get a reference to the values.
+    #[inline]
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Get a reference to the mode.
+    #[inline]
+    pub fn mode(&self) -> &M {
+        &self.mode
+    }
+
+    /// Number of the segments.
+    #[inline]
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Append `values` to the end.
+    pub fn append(&mut self, values: &[T]) {
+        if values.is_empty() {
+            return;
+        }
+        let start = self.values.len();
+        self.values.extend_from_slice(values);
+        let mut segment = self.build_segment(start, self.values.len());
+        while let Some(last) = self.segments.last() {
+            if last.end - last.start > 2 * (segment.end - segment.start) {
+                break;
+            }
+            let last = self.segments.pop().unwrap();
+            segment = self.build_segment(last.start, segment.end);
+        }
+        self.segments.push(segment);
+    }
+
+    fn build_segment(&self, start: usize, end: usize) -> Segment {
+        let sa = SuffixArray::new_by::<SAISBuilder>(&self.values[start..end], self.mode.clone());
+        let (_, indices, _) = sa.into_parts();
+        Segment {
+            start,
+            end,
+            indices,
+        }
+    }
+
+    /// Text positions where `pattern` occurs, in ascending text order.
+    pub fn find(&self, pattern: impl AsRef<[T]>) -> Vec<usize> {
+        let pattern = pattern.as_ref();
+        let mut positions = vec![];
+        for (i, segment) in self.segments.iter().enumerate() {
+            let values = &self.values[segment.start..segment.end];
+            let sa = SuffixArray::from_parts(values, &segment.indices, ());
+            if let Ok(range) = sa.search_naive(pattern) {
+                positions.extend(range.positions(&sa).map(|p| p + segment.start));
+            }
+            // the suffixes shorter than the pattern in the segment, except the last segment
+            if i + 1 < self.segments.len() && !pattern.is_empty() {
+                let tail = values.len().saturating_sub(pattern.len() - 1);
+                positions.extend(
+                    (tail..values.len())
+                        .filter(|&p| !self.mode.need_check() || self.mode.is_index(p, &values[p]))
+                        .map(|p| p + segment.start)
+                        .filter(|&p| self.values[p..].starts_with(pattern)),
+                );
+            }
+        }
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Returns true if `pattern` occurs.
+    pub fn contains(&self, pattern: impl AsRef<[T]>) -> bool {
+        !self.find(pattern).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppendableSuffixArray;
+    use crate::gens::StrIndex;
+
+    #[test]
+    fn test_append() {
+        let chunks = [
+            "ab", "cab", "", "c", "abcabca", "b", "錆さ", "び錆", "さびab", "cab",
+        ];
+        let mut sa = AppendableSuffixArray::<u8, StrIndex>::default();
+        let mut text = String::new();
+        for chunk in chunks {
+            sa.append(chunk.as_bytes());
+            text.push_str(chunk);
+            assert_eq!(sa.values(), text.as_bytes());
+            for pattern in [
+                "a",
+                "ab",
+                "bc",
+                "cabc",
+                "abcab",
+                "bca",
+                "さび",
+                "び錆さ",
+                "x",
+                "",
+            ] {
+                let expected = (0..text.len())
+                    .filter(|&p| text.is_char_boundary(p) && text[p..].starts_with(pattern))
+                    .collect::<Vec<_>>();
+                assert_eq!(sa.find(pattern), expected, "{text} {pattern}");
+            }
+        }
+        assert!(sa.segment_count() < chunks.len());
+        assert!(sa.contains("abcabcab"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Burrows–Wheeler transform (BWT) derived from a built [`SuffixArray`].
+//!
+//! The text is treated as terminated by a virtual sentinel `$` smaller than every value.
+//! The sentinel is not stored in the transformed sequence, its row is recorded as the
+//! [`primary`](`Bwt::primary`) index instead.
+
+use crate::{
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+/// Burrows–Wheeler transform of a text.
+///
+/// For a text `S` of length `n`, the rows are the `n + 1` sorted rotations of `S$`.
+/// [`sequence`](`Bwt::sequence`) is the last column without `$`,
+/// and [`primary`](`Bwt::primary`) is the row where `$` was.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Bwt<T> {
+    sequence: Vec<T>,
+    primary: usize,
+}
+
+impl<T> Bwt<T> {
+    /// Create from a transformed sequence and the row of the sentinel.
+    ///
+    /// # Panics
+    /// Panics if `primary` is out of the rows.
+    /// `primary` is never `0` unless `sequence` is empty, as the row `0` is the rotation starting with `$`.
+    pub fn from_parts(sequence: Vec<T>, primary: usize) -> Self {
+        assert!(
+            primary <= sequence.len() && (primary != 0 || sequence.is_empty()),
+            "invalid primary index {primary}"
+        );
+        Self { sequence, primary }
+    }
+
+    /// The last column of the sorted rotations without the sentinel.
+    #[inline]
+    pub fn sequence(&self) -> &[T] {
+        &self.sequence
+    }
+
+    /// The row of the sentinel in the last column.
+    #[inline]
+    pub fn primary(&self) -> usize {
+        self.primary
+    }
+
+    /// Split into the sequence and the primary index.
+    #[inline]
+    pub fn into_parts(self) -> (Vec<T>, usize) {
+        (self.sequence, self.primary)
+    }
+
+    /// Index in [`sequence`](`Bwt::sequence`) of the row, or `None` for the sentinel row.
+    #[inline]
+    pub(crate) fn row_to_index(&self, row: usize) -> Option<usize> {
+        match row.cmp(&self.primary) {
+            std::cmp::Ordering::Less => Some(row),
+            std::cmp::Ordering::Equal => None,
+            std::cmp::Ordering::Greater => Some(row - 1),
+        }
+    }
+
+    /// Restore the text by the LF-mapping, `lf(i)` is the row of the rotation one step before the row of `sequence[i]`.
+    fn inverse_by<F>(&self, lf: F) -> Vec<T>
+    where
+        T: Copy,
+        F: Fn(usize) -> usize,
+    {
+        let n = self.sequence.len();
+        let mut text = Vec::with_capacity(n);
+        // the row 0 is `$S`, whose last column is the last value of the text.
+        let mut row = 0;
+        while let Some(i) = self.row_to_index(row) {
+            text.push(self.sequence[i]);
+            row = lf(i);
+        }
+        debug_assert_eq!(text.len(), n);
+        text.reverse();
+        text
+    }
+}
+
+impl<T: Ord + Copy> Bwt<T> {
+    /// Restore the original text by the LF-mapping.
+    ///
+    /// Use [`inverse_u8`](`Bwt::inverse_u8`) for `u8`, which counts the values instead of sorting.
+    pub fn inverse(&self) -> Vec<T> {
+        // rank in the first column of each value, stable for the same values.
+        let mut order = (0..self.sequence.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| self.sequence[*i]);
+        let mut lf = vec![0; order.len()];
+        for (rank, i) in order.into_iter().enumerate() {
+            // `+ 1` for the sentinel row
+            lf[i] = rank + 1;
+        }
+        self.inverse_by(|i| lf[i])
+    }
+}
+
+impl Bwt<u8> {
+    /// Restore the original text by the LF-mapping.
+    pub fn inverse_u8(&self) -> Vec<u8> {
+        let mut heads = [0; 257];
+        for v in &self.sequence {
+            heads[*v as usize + 1] += 1;
+        }
+        // `1` for the sentinel row
+        heads[0] = 1;
+        for c in 0..256 {
+            heads[c + 1] += heads[c];
+        }
+        let lf = self
+            .sequence
+            .iter()
+            .map(|v| {
+                let h = &mut heads[*v as usize];
+                *h += 1;
+                *h - 1
+            })
+            .collect::<Vec<_>>();
+        self.inverse_by(|i| lf[i])
+    }
+}
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord + Copy,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    /// Compute the [`Bwt`] of the values.
+    ///
+    /// Returns `None` if some index is filtered by the [`IndexMode`](`crate::gens::modes::IndexMode`),
+    /// as the transform needs every suffix.
+    pub fn bwt(&self) -> Option<Bwt<T>> {
+        let values = self.values.as_ref();
+        let indices = self.indices();
+        if indices.len() != values.len() {
+            return None;
+        }
+        let Some(last) = values.last() else {
+            return Some(Bwt {
+                sequence: vec![],
+                primary: 0,
+            });
+        };
+        let mut sequence = Vec::with_capacity(values.len());
+        // the row 0 is `$S`
+        sequence.push(*last);
+        let mut primary = 0;
+        for (rank, i) in indices.iter().enumerate() {
+            match i.to_usize() {
+                0 => primary = rank + 1,
+                i => sequence.push(values[i - 1]),
+            }
+        }
+        Some(Bwt { sequence, primary })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bwt;
+    use crate::{gens::StrIndex, SuffixArray};
+
+    /// BWT by sorting the rotations, with `0` as the sentinel.
+    fn naive_bwt(text: &[u8]) -> Bwt<u8> {
+        let mut s = text.iter().map(|v| *v as u16 + 1).collect::<Vec<_>>();
+        s.push(0);
+        let n = s.len();
+        let mut rotations = (0..n).collect::<Vec<_>>();
+        rotations.sort_by_key(|i| [&s[*i..], &s[..*i]].concat());
+        let mut sequence = vec![];
+        let mut primary = 0;
+        for (row, i) in rotations.into_iter().enumerate() {
+            match s[(i + n - 1) % n] {
+                0 => primary = row,
+                v => sequence.push((v - 1) as u8),
+            }
+        }
+        Bwt { sequence, primary }
+    }
+
+    #[test]
+    fn test_bwt() {
+        let sa = SuffixArray::<_, u8, ()>::new("banana");
+        let bwt = sa.bwt().unwrap();
+        assert_eq!(bwt.sequence(), b"annbaa");
+        assert_eq!(bwt.primary(), 4);
+        for text in [
+            "",
+            "a",
+            "banana",
+            "mississippi",
+            "aaaaaaaa",
+            "abababababab",
+            "abcde錆さびacad",
+        ] {
+            let sa = SuffixArray::<_, u8, ()>::new(text);
+            let bwt = sa.bwt().unwrap();
+            assert_eq!(bwt, naive_bwt(text.as_bytes()), "{text}");
+            assert_eq!(bwt.inverse_u8(), text.as_bytes(), "{text}");
+            assert_eq!(bwt.inverse(), text.as_bytes(), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_bwt_generic() {
+        let text = "錆さび錆さびさびabab".chars().collect::<Vec<_>>();
+        let sa = SuffixArray::<_, char, ()>::new(text.as_slice());
+        let bwt = sa.bwt().unwrap();
+        assert_eq!(bwt.inverse(), text);
+        let bwt = Bwt::from_parts(bwt.sequence().to_vec(), bwt.primary());
+        assert_eq!(bwt.inverse(), text);
+    }
+
+    #[test]
+    fn test_bwt_filtered() {
+        let sa = SuffixArray::<_, u8, StrIndex>::new("錆さび");
+        assert_eq!(sa.bwt(), None);
+        let sa = SuffixArray::<_, u8, StrIndex>::new("abc");
+        assert!(sa.bwt().is_some());
+    }
+}
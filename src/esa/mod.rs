@@ -0,0 +1,382 @@
+//! Enhanced suffix array, a virtual suffix tree over a [`SuffixArray`].
+//!
+//! The internal nodes are the lcp-intervals of the [`LcpArray`], and the leaves are the suffixes.
+//! Children are enumerated with the child table (`up`, `down` and `next_l`) of Abouelhoda et al.,
+//! and the parent is found with the previous and next smaller lcp values.
+//!
+//! As there is no terminator, a suffix which is a prefix of another suffix is a leaf
+//! with the same string depth as its parent.
+
+use std::ops::Range;
+
+use crate::{
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex},
+    lcp::LcpArray,
+    search::Positions,
+    SuffixArray,
+};
+
+/// Marker for no index in the tables.
+const NONE: usize = usize::MAX;
+
+/// Node of the virtual suffix tree.
+///
+/// An internal node is an lcp-interval, the ranks of the suffixes sharing a prefix of [`depth`](`Node::depth`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Node {
+    lb: usize,
+    rb: usize,
+    depth: usize,
+}
+
+impl Node {
+    /// Ranks of the leaves under this node.
+    #[inline]
+    pub fn ranks(&self) -> Range<usize> {
+        self.lb..self.rb + 1
+    }
+
+    /// Number of the leaves under this node.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rb + 1 - self.lb
+    }
+
+    /// Always false, a node has at least one leaf.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns true if this node is a leaf, a single suffix.
+    #[inline]
+    pub fn is_leaf(&self) -> bool {
+        self.lb == self.rb
+    }
+
+    /// Length of the string from the root to this node.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+/// [`SuffixArray`] with its [`LcpArray`] and child table.
+#[derive(Debug, Clone)]
+pub struct EnhancedSuffixArray<'a, B, T, M, I = Vec<usize>> {
+    sa: &'a SuffixArray<B, T, M, I>,
+    lcp: LcpArray,
+    // tables over the ranks `0..=n`, on the lcp values shifted by one
+    // so that the ranks `0` and `n` are smaller than any lcp.
+    up: Vec<usize>,
+    down: Vec<usize>,
+    next_l: Vec<usize>,
+    prev_smaller: Vec<usize>,
+    next_smaller: Vec<usize>,
+}
+
+impl<'a, B, T, M, I> EnhancedSuffixArray<'a, B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    /// Build the enhanced suffix array of `sa`.
+    pub fn new(sa: &'a SuffixArray<B, T, M, I>) -> Self
+    where
+        M: IndexMode<T>,
+    {
+        Self::from_lcp(sa, sa.lcp())
+    }
+
+    /// Build from `sa` and its already built [`LcpArray`].
+    pub fn from_lcp(sa: &'a SuffixArray<B, T, M, I>, lcp: LcpArray) -> Self {
+        let n = lcp.len();
+        assert_eq!(n, sa.indices().len());
+        let key = |i: usize| if i == 0 || i == n { 0 } else { lcp[i] + 1 };
+        let mut up = vec![NONE; n + 1];
+        let mut down = vec![NONE; n + 1];
+        let mut next_l = vec![NONE; n + 1];
+        let mut prev_smaller = vec![NONE; n + 1];
+        let mut next_smaller = vec![NONE; n + 1];
+
+        let mut stack = vec![0];
+        let mut last = NONE;
+        for i in 1..=n {
+            while let Some(&top) = stack.last() {
+                if key(i) >= key(top) {
+                    break;
+                }
+                last = top;
+                stack.pop();
+                next_smaller[last] = i;
+                let top = *stack.last().unwrap();
+                if key(i) <= key(top) && key(top) != key(last) {
+                    down[top] = last;
+                }
+            }
+            if last != NONE {
+                up[i] = last;
+                last = NONE;
+            }
+            let top = *stack.last().unwrap();
+            if key(i) == key(top) {
+                next_l[top] = i;
+                prev_smaller[i] = prev_smaller[top];
+            } else {
+                prev_smaller[i] = top;
+            }
+            stack.push(i);
+        }
+
+        Self {
+            sa,
+            lcp,
+            up,
+            down,
+            next_l,
+            prev_smaller,
+            next_smaller,
+        }
+    }
+
+    /// Get a reference to the suffix array.
+    #[inline]
+    pub fn suffix_array(&self) -> &'a SuffixArray<B, T, M, I> {
+        self.sa
+    }
+
+    /// Get a reference to the lcp array.
+    #[inline]
+    pub fn lcp(&self) -> &LcpArray {
+        &self.lcp
+    }
+
+    /// The root node, or [`None`] if the array is empty.
+    pub fn root(&self) -> Option<Node> {
+        let n = self.lcp.len();
+        (n > 0).then(|| self.node(0, n - 1))
+    }
+
+    /// The node of the ranks `lb..=rb`, which should be a leaf or an lcp-interval.
+    fn node(&self, lb: usize, rb: usize) -> Node {
+        let depth = if lb == rb {
+            self.sa.values().as_ref().len() - self.sa.indices()[lb].to_usize()
+        } else {
+            self.lcp[self.first_l_index(lb, rb)]
+        };
+        Node { lb, rb, depth }
+    }
+
+    /// The first rank in `lb + 1..=rb` having the lcp value of the interval.
+    fn first_l_index(&self, lb: usize, rb: usize) -> usize {
+        let up = self.up[rb + 1];
+        if lb < up && up <= rb {
+            up
+        } else {
+            self.down[lb]
+        }
+    }
+
+    /// Iterate the children of `node` in suffix order.
+    pub fn children(&self, node: Node) -> Children<'_, 'a, B, T, M, I> {
+        let next = if node.is_leaf() {
+            NONE
+        } else {
+            self.first_l_index(node.lb, node.rb)
+        };
+        Children {
+            esa: self,
+            lb: if node.is_leaf() { NONE } else { node.lb },
+            next,
+            rb: node.rb,
+        }
+    }
+
+    /// The parent of `node`, or [`None`] if `node` is the root.
+    pub fn parent(&self, node: Node) -> Option<Node> {
+        let n = self.lcp.len();
+        let key = |i: usize| if i == 0 || i == n { 0 } else { self.lcp[i] + 1 };
+        // the parent has an l-index at `lb` or `rb + 1`, with the larger lcp value.
+        let l_index = if key(node.lb) >= key(node.rb + 1) {
+            node.lb
+        } else {
+            node.rb + 1
+        };
+        if key(l_index) == 0 {
+            return None;
+        }
+        Some(Node {
+            lb: self.prev_smaller[l_index],
+            rb: self.next_smaller[l_index] - 1,
+            depth: key(l_index) - 1,
+        })
+    }
+
+    /// Iterate all nodes in preorder, the leaves included.
+    pub fn nodes(&self) -> Nodes<'_, 'a, B, T, M, I> {
+        Nodes {
+            esa: self,
+            stack: self.root().into_iter().collect(),
+        }
+    }
+
+    /// Values on the path from the root to `node`.
+    pub fn label(&self, node: Node) -> &'a [T] {
+        let start = self.sa.indices()[node.lb].to_usize();
+        &self.sa.values().as_ref()[start..start + node.depth]
+    }
+
+    /// Text positions of the leaves under `node`, in suffix order.
+    pub fn positions(&self, node: Node) -> Positions<'a, I::Index> {
+        crate::search::MatchRange::new(node.lb, node.rb + 1).positions(self.sa)
+    }
+}
+
+/// Iterator of the children of a node. Created by [`EnhancedSuffixArray::children`].
+#[derive(Debug)]
+pub struct Children<'e, 'a, B, T, M, I> {
+    esa: &'e EnhancedSuffixArray<'a, B, T, M, I>,
+    lb: usize,
+    next: usize,
+    rb: usize,
+}
+
+impl<B, T, M, I> Iterator for Children<'_, '_, B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        if self.lb == NONE {
+            return None;
+        }
+        let lb = self.lb;
+        if self.next == NONE || self.next > self.rb {
+            self.lb = NONE;
+            return Some(self.esa.node(lb, self.rb));
+        }
+        let rb = self.next - 1;
+        self.lb = self.next;
+        self.next = self.esa.next_l[self.next];
+        Some(self.esa.node(lb, rb))
+    }
+}
+
+/// Preorder iterator of the nodes. Created by [`EnhancedSuffixArray::nodes`].
+#[derive(Debug)]
+pub struct Nodes<'e, 'a, B, T, M, I> {
+    esa: &'e EnhancedSuffixArray<'a, B, T, M, I>,
+    stack: Vec<Node>,
+}
+
+impl<B, T, M, I> Iterator for Nodes<'_, '_, B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Node> {
+        let node = self.stack.pop()?;
+        let len = self.stack.len();
+        self.stack.extend(self.esa.children(node));
+        self.stack[len..].reverse();
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{EnhancedSuffixArray, Node};
+    use crate::{
+        gens::{IndexMode, StrIndex},
+        SuffixArray,
+    };
+
+    /// All lcp-intervals and leaves by definition.
+    fn naive_nodes(sa: &SuffixArray<&str, u8, impl IndexMode<u8>>) -> BTreeSet<Node> {
+        let lcp = sa.lcp();
+        let n = lcp.len();
+        let text_len = sa.values().len();
+        let mut nodes = BTreeSet::new();
+        for lb in 0..n {
+            nodes.insert(Node {
+                lb,
+                rb: lb,
+                depth: text_len - sa.indices()[lb],
+            });
+            for rb in lb + 1..n {
+                let depth = *lcp[lb + 1..=rb].iter().min().unwrap();
+                let left = lb == 0 || lcp[lb] < depth;
+                let right = rb == n - 1 || lcp[rb + 1] < depth;
+                if left && right {
+                    nodes.insert(Node { lb, rb, depth });
+                }
+            }
+        }
+        nodes
+    }
+
+    fn check<M: IndexMode<u8>>(value: &str, mode: M) {
+        let sa = SuffixArray::new_by::<crate::gens::builders::NaiveBuilder>(value, mode);
+        let esa = EnhancedSuffixArray::new(&sa);
+        let nodes = esa.nodes().collect::<Vec<_>>();
+        assert_eq!(
+            nodes.iter().copied().collect::<BTreeSet<_>>(),
+            naive_nodes(&sa)
+        );
+        assert_eq!(nodes.len(), naive_nodes(&sa).len(), "{value}");
+        let root = esa.root();
+        assert_eq!(root, nodes.first().copied());
+        for node in nodes {
+            let children = esa.children(node).collect::<Vec<_>>();
+            if node.is_leaf() {
+                assert!(children.is_empty());
+            } else {
+                assert!(children.len() >= 2, "{value} {node:?}");
+                assert_eq!(children.first().unwrap().ranks().start, node.ranks().start);
+                assert_eq!(children.last().unwrap().ranks().end, node.ranks().end);
+                for w in children.windows(2) {
+                    assert_eq!(w[0].ranks().end, w[1].ranks().start);
+                }
+            }
+            for child in children {
+                assert!(child.depth() >= node.depth());
+                assert_eq!(esa.parent(child), Some(node), "{value} {child:?}");
+                let label = esa.label(child);
+                assert!(esa
+                    .positions(child)
+                    .all(|p| value.as_bytes()[p..].starts_with(label)));
+            }
+        }
+        if let Some(root) = root {
+            assert_eq!(esa.parent(root), None);
+            assert_eq!(root.len(), sa.indices().len());
+        }
+    }
+
+    #[test]
+    fn test_esa() {
+        for value in [
+            "",
+            "a",
+            "ab",
+            "aaaa",
+            "mississippi",
+            "abcabcabcabx",
+            "acgtacgtaacgtttgacgatacgacgtacgtagctagcatcg",
+            "錆さび錆さびさびab",
+        ] {
+            check(value, ());
+            check(value, StrIndex);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! FM-index over a [`SuffixArray`].
+//!
+//! [`FmIndex`] keeps the [`Bwt`] in a wavelet matrix over the codes of the distinct values,
+//! which answers both the values and their ranks in `⌈log2 σ⌉` bits per value, and the C array,
+//! so [`count`](`FmIndex::count`) runs in `O(m)` rank queries by backward search, without the text.
+//! Each query descends the `⌈log2 σ⌉` levels of the matrix, so the count takes `O(m log σ)` time.
+//! [`locate`](`FmIndex::locate`) recovers the positions from a suffix array sampled at every
+//! `sample_rate` text positions, walking at most `sample_rate - 1` LF steps for each.
+
+use crate::{
+    bwt::Bwt,
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex},
+    search::MatchRange,
+    SuffixArray,
+};
+use std::mem::size_of;
+
+mod rank;
+pub(crate) use rank::*;
+
+/// FM-index of a text of ordered values, `u8` by default.
+#[derive(Debug, Clone)]
+pub struct FmIndex<T = u8> {
+    /// distinct values of the text in order, the code of a value is its position
+    alphabet: Vec<T>,
+    /// codes of the last column without the sentinel
+    occ: WaveletMatrix,
+    /// row of the sentinel in the last column
+    primary: usize,
+    /// `c[code]` is the first row starting with the value of `code`, the row 0 is the sentinel.
+    c: Vec<usize>,
+    /// rows whose text position is sampled
+    sampled: RankBits,
+    /// sampled text positions in row order
+    samples: Vec<usize>,
+    sample_rate: usize,
+}
+
+impl<T: Ord + Clone> FmIndex<T> {
+    /// Build from a [`SuffixArray`], sampling every `sample_rate` text positions for [`locate`](`FmIndex::locate`).
+    ///
+    /// Returns `None` if some index is filtered by the [`IndexMode`],
+    /// as the index needs every suffix.
+    ///
+    /// # Panics
+    /// Panics if `sample_rate` is `0`.
+    pub fn new<B, M, I>(sa: &SuffixArray<B, T, M, I>, sample_rate: usize) -> Option<Self>
+    where
+        B: AsRef<[T]>,
+        M: IndexMode<T>,
+        I: IndexStorage,
+    {
+        assert_ne!(sample_rate, 0, "sample rate should be positive");
+        let values = sa.values().as_ref();
+        if sa.indices().len() != values.len() {
+            return None;
+        }
+        Some(Self::from_suffixes(
+            values,
+            sa.indices().iter().map(|i| i.to_usize()),
+            sample_rate,
+            |pos| pos % sample_rate == 0,
+        ))
+    }
+
+    /// Build from every suffix of `values` in the sorted order, sampling the text positions where `sample` holds.
+    ///
+    /// [`locate_rank`](`FmIndex::locate_rank`) walks from each position back to a sample or to the position `0`.
+    pub(crate) fn from_suffixes<S, F>(
+        values: &[T],
+        suffixes: S,
+        sample_rate: usize,
+        sample: F,
+    ) -> Self
+    where
+        S: Iterator<Item = usize>,
+        F: Fn(usize) -> bool,
+    {
+        let n = values.len();
+        let mut alphabet = values.to_vec();
+        alphabet.sort_unstable();
+        alphabet.dedup();
+        let code = |v: &T| {
+            alphabet
+                .binary_search(v)
+                .expect("every value is in the alphabet")
+        };
+        let mut codes = Vec::with_capacity(n);
+        let mut c = vec![0; alphabet.len() + 1];
+        let mut primary = 0;
+        let mut sampled = RankBitsBuilder::new(n + 1);
+        let mut samples = vec![];
+        // the row 0 is the sentinel at n, the other rows are shifted by one from the suffix array.
+        for (row, pos) in std::iter::once(n).chain(suffixes).enumerate() {
+            if pos == 0 {
+                primary = row;
+            } else {
+                let code = code(&values[pos - 1]);
+                codes.push(code);
+                c[code + 1] += 1;
+            }
+            if pos < n && sample(pos) {
+                sampled.set(row);
+                samples.push(pos);
+            }
+        }
+        c[0] = 1;
+        for code in 0..alphabet.len() {
+            c[code + 1] += c[code];
+        }
+        let occ = WaveletMatrix::new(codes, alphabet.len());
+        Self {
+            alphabet,
+            occ,
+            primary,
+            c,
+            sampled: sampled.build(),
+            samples,
+            sample_rate,
+        }
+    }
+
+    /// Length of the indexed text.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.occ.len()
+    }
+
+    /// Returns true if the indexed text is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Text positions between the samples.
+    #[inline]
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Restore the [`Bwt`] of the text from the wavelet matrix.
+    pub fn bwt(&self) -> Bwt<T> {
+        let sequence = (0..self.len())
+            .map(|i| self.alphabet[self.occ.get(i)].clone())
+            .collect();
+        Bwt::from_parts(sequence, self.primary)
+    }
+
+    /// Bytes of the index, the heap and the inline fields.
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.alphabet.len() * size_of::<T>()
+            + self.occ.size_in_bytes()
+            + self.c.len() * size_of::<usize>()
+            + self.sampled.size_in_bytes()
+            + self.samples.len() * size_of::<usize>()
+    }
+
+    /// Number of the values smaller than `v` in the text.
+    #[inline]
+    pub fn c(&self, v: T) -> usize {
+        let (Ok(code) | Err(code)) = self.alphabet.binary_search(&v);
+        // minus the sentinel
+        self.c[code] - 1
+    }
+
+    /// Index in the last column without the sentinel of `row`.
+    #[inline]
+    fn row_to_index(&self, row: usize) -> usize {
+        if row > self.primary {
+            row - 1
+        } else {
+            row
+        }
+    }
+
+    /// Row of the rotation one step before `row`. `row` must not be the sentinel row.
+    #[inline]
+    pub(crate) fn lf(&self, row: usize) -> usize {
+        assert_ne!(row, self.primary, "lf on the sentinel row");
+        let (code, rank) = self.occ.get_rank(self.row_to_index(row));
+        self.c[code] + rank
+    }
+
+    /// Search `pattern` by backward search in `O(m log σ)` time for `m` values.
+    ///
+    /// The returned range is the ranks of the suffix array this index is built from.
+    pub fn backward_search<P: AsRef<[T]>>(&self, pattern: P) -> MatchRange {
+        let (mut start, mut end) = (0, self.len() + 1);
+        let pattern = pattern.as_ref();
+        if pattern.is_empty() {
+            return MatchRange::new(0, self.len());
+        }
+        for v in pattern.iter().rev() {
+            let Ok(code) = self.alphabet.binary_search(v) else {
+                return MatchRange::default();
+            };
+            start = self.c[code] + self.occ.rank(code, self.row_to_index(start));
+            end = self.c[code] + self.occ.rank(code, self.row_to_index(end));
+            if start >= end {
+                return MatchRange::default();
+            }
+        }
+        // rows to ranks, a nonempty pattern never matches the sentinel row.
+        MatchRange::new(start - 1, end - 1)
+    }
+
+    /// Count the occurrences of `pattern` in `O(m log σ)` time for `m` values.
+    #[inline]
+    pub fn count<P: AsRef<[T]>>(&self, pattern: P) -> usize {
+        self.backward_search(pattern).len()
+    }
+
+    /// Returns true if `pattern` occurs in the text.
+    #[inline]
+    pub fn contains<P: AsRef<[T]>>(&self, pattern: P) -> bool {
+        !self.backward_search(pattern).is_empty()
+    }
+
+    /// Text position of the suffix at `rank` of the suffix array.
+    ///
+    /// # Panics
+    /// Panics if `rank` is out of the text.
+    pub fn locate_rank(&self, rank: usize) -> usize {
+        assert!(rank < self.len(), "rank {rank} is out of range");
+        let mut row = rank + 1;
+        let mut steps = 0;
+        while !self.sampled.get(row) {
+            if row == self.primary {
+                // the position 0
+                return steps;
+            }
+            row = self.lf(row);
+            steps += 1;
+        }
+        self.samples[self.sampled.rank(row)] + steps
+    }
+
+    /// Text positions of the occurrences of `pattern`, in suffix order.
+    pub fn locate<P: AsRef<[T]>>(&self, pattern: P) -> Vec<usize> {
+        self.backward_search(pattern)
+            .ranks()
+            .map(|rank| self.locate_rank(rank))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FmIndex;
+    use crate::{gens::StrIndex, SuffixArray};
+
+    fn naive_positions(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..text.len())
+            .filter(|i| text[*i..].starts_with(pattern))
+            .collect()
+    }
+
+    #[test]
+    fn test_fm_index() {
+        let text = "mississippi_abracadabra_錆さび錆さびさび_aaaaaaaaaaabababab";
+        let sa = SuffixArray::<_, u8, ()>::new(text);
+        for rate in [1, 2, 3, 7, 32, 1000] {
+            let fm = FmIndex::new(&sa, rate).unwrap();
+            assert_eq!(fm.len(), text.len());
+            for rank in 0..text.len() {
+                assert_eq!(fm.locate_rank(rank), sa.indices()[rank]);
+            }
+            for pattern in [
+                "", "i", "ss", "issi", "abra", "さび", "aaaa", "abab", "x", "pix",
+            ] {
+                let expected = naive_positions(text.as_bytes(), pattern.as_bytes());
+                assert_eq!(fm.count(pattern), expected.len(), "{pattern}");
+                if !pattern.is_empty() {
+                    assert_eq!(
+                        fm.backward_search(pattern),
+                        sa.search_naive(pattern).unwrap_or_default()
+                    );
+                }
+                let mut located = fm.locate(pattern);
+                located.sort_unstable();
+                assert_eq!(located, expected, "{pattern}");
+            }
+        }
+        let fm = FmIndex::new(&sa, 4).unwrap();
+        assert_eq!(fm.c(b'b'), text.bytes().filter(|v| *v < b'b').count());
+        assert_eq!(Some(fm.bwt()), sa.bwt());
+    }
+
+    #[test]
+    fn test_fm_index_compressed() {
+        let mut state = 1u32;
+        let text = (0..1 << 16)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                b"ACGT"[(state >> 16) as usize % 4]
+            })
+            .collect::<Vec<_>>();
+        let sa = SuffixArray::<_, u8, ()>::new(&text[..]);
+        let fm = FmIndex::new(&sa, 32).unwrap();
+        // 2 bits for each value, the rank counts and the samples are under a byte for each value.
+        assert!(
+            fm.size_in_bytes() < text.len(),
+            "{} bytes for {} values",
+            fm.size_in_bytes(),
+            text.len()
+        );
+        assert_eq!(Some(fm.bwt()), sa.bwt());
+        for pattern in ["ACGT", "GATTACA", "TTTTTTTT"] {
+            assert_eq!(
+                fm.count(pattern),
+                naive_positions(&text, pattern.as_bytes()).len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fm_index_edge() {
+        let fm = FmIndex::new(&SuffixArray::<_, u8, ()>::new(""), 4).unwrap();
+        assert_eq!(fm.count("a"), 0);
+        assert!(fm.locate("").is_empty());
+        let fm = FmIndex::new(&SuffixArray::<_, u8, ()>::new("a"), 4).unwrap();
+        assert_eq!(fm.locate("a"), [0]);
+        assert!(FmIndex::new(&SuffixArray::<_, u8, StrIndex>::new("錆"), 4).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+use std::mem::size_of;
+
+/// Wavelet matrix over a sequence of codes `0..sigma`, answering the codes and their ranks in `O(log σ)`.
+///
+/// Each code takes `⌈log2 σ⌉` bits plus the counts of [`RankBits`].
+#[derive(Debug, Clone)]
+pub(crate) struct WaveletMatrix {
+    /// bits of the codes from the most significant one, each level in the order sorted by the previous bits
+    levels: Vec<RankBits>,
+    /// number of zeros in each level
+    zeros: Vec<usize>,
+    /// position of the first of each code after the last level
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl WaveletMatrix {
+    pub(crate) fn new(codes: Vec<usize>, sigma: usize) -> Self {
+        let depth = match sigma {
+            0 => 0,
+            _ => (usize::BITS - (sigma - 1).leading_zeros()) as usize,
+        };
+        let len = codes.len();
+        let mut current = codes;
+        let mut next = Vec::with_capacity(current.len());
+        let mut levels = Vec::with_capacity(depth);
+        let mut zeros = Vec::with_capacity(depth);
+        for level in 0..depth {
+            let shift = depth - 1 - level;
+            let mut bits = RankBitsBuilder::new(current.len());
+            for (i, code) in current.iter().enumerate() {
+                if code >> shift & 1 == 1 {
+                    bits.set(i);
+                }
+            }
+            // stable partition by the bit, zeros first.
+            next.clear();
+            next.extend(current.iter().filter(|code| *code >> shift & 1 == 0));
+            zeros.push(next.len());
+            next.extend(current.iter().filter(|code| *code >> shift & 1 == 1));
+            std::mem::swap(&mut current, &mut next);
+            levels.push(bits.build());
+        }
+        let mut wm = Self {
+            levels,
+            zeros,
+            starts: vec![],
+            len,
+        };
+        wm.starts = (0..sigma).map(|code| wm.descend(code, 0)).collect();
+        wm
+    }
+
+    /// Position after the last level of the `i`-th position among the codes before it equal to `code`.
+    #[inline]
+    fn descend(&self, code: usize, mut i: usize) -> usize {
+        let depth = self.levels.len();
+        for (level, (bits, zeros)) in self.levels.iter().zip(&self.zeros).enumerate() {
+            let ones = bits.rank(i);
+            i = if code >> (depth - 1 - level) & 1 == 1 {
+                zeros + ones
+            } else {
+                i - ones
+            };
+        }
+        i
+    }
+
+    /// Length of the sequence.
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of `code` in `sequence[..i]`.
+    #[inline]
+    pub(crate) fn rank(&self, code: usize, i: usize) -> usize {
+        self.descend(code, i) - self.starts[code]
+    }
+
+    /// The code at `i` and the number of it in `sequence[..i]`.
+    #[inline]
+    pub(crate) fn get_rank(&self, mut i: usize) -> (usize, usize) {
+        let mut code = 0;
+        for (bits, zeros) in self.levels.iter().zip(&self.zeros) {
+            let ones = bits.rank(i);
+            if bits.get(i) {
+                code = code << 1 | 1;
+                i = zeros + ones;
+            } else {
+                code <<= 1;
+                i -= ones;
+            }
+        }
+        (code, i - self.starts[code])
+    }
+
+    /// The code at `i`.
+    #[inline]
+    pub(crate) fn get(&self, i: usize) -> usize {
+        self.get_rank(i).0
+    }
+
+    /// Bytes of the heap.
+    pub(crate) fn size_in_bytes(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|level| size_of::<RankBits>() + level.size_in_bytes())
+            .sum::<usize>()
+            + (self.zeros.len() + self.starts.len()) * size_of::<usize>()
+    }
+}
+
+/// Bit vector with constant time rank.
+///
+/// The counts are kept for every [`RankBits::BLOCK`] words, an eighth of the bits.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RankBits {
+    words: Vec<u64>,
+    /// number of ones before each block
+    blocks: Vec<u64>,
+}
+
+impl RankBits {
+    const BLOCK: usize = 8;
+
+    #[inline]
+    pub(crate) fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    /// Number of ones before `i`.
+    #[inline]
+    pub(crate) fn rank(&self, i: usize) -> usize {
+        let (w, block) = (i / 64, i / 64 / Self::BLOCK);
+        let mask = (1u64 << (i % 64)) - 1;
+        let full = self.words[block * Self::BLOCK..w]
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum::<usize>();
+        self.blocks[block] as usize + full + (self.words[w] & mask).count_ones() as usize
+    }
+
+    /// Position of the `k`-th one from `0`, `k` must be less than the ones.
+    pub(crate) fn select(&self, mut k: usize) -> usize {
+        let block = self.blocks.partition_point(|r| *r as usize <= k) - 1;
+        k -= self.blocks[block] as usize;
+        for (w, word) in self.words.iter().enumerate().skip(block * Self::BLOCK) {
+            let ones = word.count_ones() as usize;
+            if k < ones {
+                let mut word = *word;
+                for _ in 0..k {
+                    // clear the lowest one
+                    word &= word - 1;
+                }
+                return w * 64 + word.trailing_zeros() as usize;
+            }
+            k -= ones;
+        }
+        panic!("select out of the ones")
+    }
+
+    /// Bytes of the heap.
+    pub(crate) fn size_in_bytes(&self) -> usize {
+        (self.words.len() + self.blocks.len()) * size_of::<u64>()
+    }
+}
+
+/// Builder of [`RankBits`].
+#[derive(Debug, Clone)]
+pub(crate) struct RankBitsBuilder {
+    words: Vec<u64>,
+}
+
+impl RankBitsBuilder {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len / 64 + 1],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    pub(crate) fn build(self) -> RankBits {
+        let mut rank = 0;
+        let blocks = self
+            .words
+            .chunks(RankBits::BLOCK)
+            .map(|block| {
+                let r = rank;
+                rank += block.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+                r
+            })
+            .collect();
+        RankBits {
+            words: self.words,
+            blocks,
+        }
+    }
+}
@@ -0,0 +1,556 @@
+use std::{any::TypeId, fmt, marker::PhantomData, mem::size_of, str::FromStr};
+
+#[cfg(feature = "parallel")]
+use super::{builders::ParallelBuilder, parallel::Threads};
+use super::{
+    builders::{
+        BucketBuilder, BuildError, BuildObserver, Builder, NaiveBuilder, PeakMemory, SAISBuilder,
+        SAISBuilderU8, TwoStageBuilder, TwoStageBuilderU8,
+    },
+    error::{check_build, try_vec},
+    observer::Progress,
+    sais::sais,
+    simple::empty_index,
+    two_stage::two_stage,
+    IndexMode,
+};
+use crate::{
+    indices::{SaIndex, U40},
+    SuffixArray,
+};
+
+/// Algorithm to build a [`SuffixArray`], selectable at runtime.
+///
+/// Each variant dispatches to the builder of the same name,
+/// e.g. it can be parsed from a config file by [`FromStr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Algorithm {
+    /// [`NaiveBuilder`]
+    Naive,
+    /// [`BucketBuilder`]
+    Bucket,
+    /// [`TwoStageBuilder`], or [`TwoStageBuilderU8`] for bytes.
+    TwoStage,
+    /// [`TwoStageBuilderU8`], failing with [`BuildError::UnsupportedValues`] except for bytes.
+    TwoStageU8,
+    /// [`SAISBuilder`], or [`SAISBuilderU8`] for bytes.
+    #[default]
+    SAIS,
+    /// [`SAISBuilderU8`], failing with [`BuildError::UnsupportedValues`] except for bytes.
+    SAISU8,
+    /// [`ParallelBuilder`](`super::builders::ParallelBuilder`), available with the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    Parallel,
+}
+
+impl Algorithm {
+    /// Every algorithm.
+    pub const ALL: &'static [Self] = &[
+        Self::Naive,
+        Self::Bucket,
+        Self::TwoStage,
+        Self::TwoStageU8,
+        Self::SAIS,
+        Self::SAISU8,
+        #[cfg(feature = "parallel")]
+        Self::Parallel,
+    ];
+
+    /// The name parsed by [`FromStr`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Naive => "naive",
+            Self::Bucket => "bucket",
+            Self::TwoStage => "two-stage",
+            Self::TwoStageU8 => "two-stage-u8",
+            Self::SAIS => "sais",
+            Self::SAISU8 => "sais-u8",
+            #[cfg(feature = "parallel")]
+            Self::Parallel => "parallel",
+        }
+    }
+
+    /// [`PeakMemory`] of the builder with `usize` entries.
+    pub fn peak_memory(self, len: usize) -> usize {
+        self.peak_memory_width(len, size_of::<usize>())
+    }
+
+    /// [`PeakMemory`] of the builder with the entries of `width` bytes.
+    pub fn peak_memory_width(self, len: usize, width: usize) -> usize {
+        match self {
+            Self::Naive => NaiveBuilder::peak_memory_width(len, width),
+            Self::Bucket => BucketBuilder::peak_memory_width(len, width),
+            Self::TwoStage => TwoStageBuilder::peak_memory_width(len, width),
+            Self::TwoStageU8 => TwoStageBuilderU8::peak_memory_width(len, width),
+            Self::SAIS => SAISBuilder::peak_memory_width(len, width),
+            Self::SAISU8 => SAISBuilderU8::peak_memory_width(len, width),
+            #[cfg(feature = "parallel")]
+            Self::Parallel => ParallelBuilder::peak_memory_width(len, width),
+        }
+    }
+
+    /// The algorithm building the values of `T`,
+    /// [`TwoStage`](`Algorithm::TwoStage`) and [`SAIS`](`Algorithm::SAIS`) take their byte builders for [`u8`].
+    pub fn for_values<T: 'static>(self) -> Self {
+        match self {
+            Self::TwoStage if is_bytes::<T>() => Self::TwoStageU8,
+            Self::SAIS if is_bytes::<T>() => Self::SAISU8,
+            algorithm => algorithm,
+        }
+    }
+
+    /// Build on `threads` if the algorithm is parallel.
+    fn try_build_in<B, T, Im, X, O>(
+        self,
+        values: B,
+        mode: Im,
+        #[cfg(feature = "parallel")] threads: Threads<'_>,
+        observer: &mut O,
+    ) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError>
+    where
+        T: SelectValue,
+        B: AsRef<[T]>,
+        Im: IndexMode<T>,
+        X: SaIndex,
+        O: BuildObserver + ?Sized,
+    {
+        match self.for_values::<T>() {
+            Self::Naive => NaiveBuilder::try_build_observed(values, mode, observer),
+            Self::Bucket => BucketBuilder::try_build_observed(values, mode, observer),
+            Self::TwoStage => {
+                check_build::<T, Im, X>(values.as_ref(), &mode)?;
+                SuffixArray::new_two_stage_observed(values, mode, &mut Progress::new(observer))
+            }
+            Self::TwoStageU8 => build_bytes(values, mode, observer, |bytes, progress| {
+                two_stage(bytes, u8::MAX as usize, progress)
+            }),
+            Self::SAIS => SAISBuilder::try_build_observed(values, mode, observer),
+            Self::SAISU8 => build_bytes(values, mode, observer, |bytes, progress| {
+                let mut indices = try_vec(empty_index(), bytes.len())?;
+                sais(bytes, &mut indices, u8::MAX as usize, progress)?;
+                Ok(indices)
+            }),
+            #[cfg(feature = "parallel")]
+            Self::Parallel => SuffixArray::try_new_parallel(values, mode, threads, observer),
+        }
+    }
+}
+
+/// The values built by [`Algorithm`] and [`BuildOptions`], also [`Sync`] with the `parallel` feature.
+#[cfg(feature = "parallel")]
+pub trait SelectValue: Ord + Sync + 'static {}
+
+#[cfg(feature = "parallel")]
+impl<T: Ord + Sync + 'static> SelectValue for T {}
+
+/// The values built by [`Algorithm`] and [`BuildOptions`], also [`Sync`] with the `parallel` feature.
+#[cfg(not(feature = "parallel"))]
+pub trait SelectValue: Ord + 'static {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T: Ord + 'static> SelectValue for T {}
+
+#[inline]
+fn is_bytes<T: 'static>() -> bool {
+    TypeId::of::<T>() == TypeId::of::<u8>()
+}
+
+/// View `values` as bytes if `T` is [`u8`].
+pub(crate) fn as_bytes<T: 'static>(values: &[T]) -> Option<&[u8]> {
+    // SAFETY: `T` is `u8`.
+    is_bytes::<T>()
+        .then(|| unsafe { std::slice::from_raw_parts(values.as_ptr().cast::<u8>(), values.len()) })
+}
+
+/// Build the values by `build` over them as bytes,
+/// or fail with [`BuildError::UnsupportedValues`] if `T` is not [`u8`].
+fn build_bytes<B, T, Im, X, O>(
+    values: B,
+    mode: Im,
+    observer: &mut O,
+    build: impl FnOnce(&[u8], &mut Progress<'_, O>) -> Result<Vec<X>, BuildError>,
+) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError>
+where
+    T: Ord + 'static,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+    X: SaIndex,
+    O: BuildObserver + ?Sized,
+{
+    let source = values.as_ref();
+    let bytes = as_bytes(source).ok_or(BuildError::UnsupportedValues)?;
+    check_build::<T, Im, X>(source, &mode)?;
+    let mut progress = Progress::new(observer);
+    let mut indices = build(bytes, &mut progress)?;
+    SuffixArray::<B, T, Im, Vec<X>>::gen_check(source, &indices);
+    SuffixArray::<B, T, Im, Vec<X>>::check_remove_index(
+        source,
+        &mut indices,
+        &mode,
+        &mut progress,
+    )?;
+    Ok(SuffixArray {
+        values,
+        indices,
+        mode,
+        value_type: PhantomData,
+    })
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error on parsing an [`Algorithm`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAlgorithmError(String);
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown algorithm {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseAlgorithmError {}
+
+/// Parses the [`name`](`Algorithm::name`) ignoring the case, and `_` in place of `-`.
+impl FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name().eq_ignore_ascii_case(&s.replace('_', "-")))
+            .ok_or_else(|| ParseAlgorithmError(s.to_string()))
+    }
+}
+
+impl<T, B, Im> Builder<B, T, Im> for Algorithm
+where
+    T: SelectValue,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    /// Build by the default algorithm, [`SAIS`](`Algorithm::SAIS`).
+    #[inline]
+    fn build(values: B, mode: Im) -> SuffixArray<B, T, Im> {
+        Self::default().build_with(values, mode)
+    }
+
+    /// # Panics
+    /// Panics if [`try_build_with`](`Builder::try_build_with`) fails.
+    fn build_with(&self, values: B, mode: Im) -> SuffixArray<B, T, Im> {
+        match self.try_build_with(values, mode) {
+            Ok(sa) => sa,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn try_build_observed_with<X: SaIndex, O: BuildObserver + ?Sized>(
+        &self,
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        self.try_build_in(
+            values,
+            mode,
+            #[cfg(feature = "parallel")]
+            Threads::default(),
+            observer,
+        )
+    }
+}
+
+/// Width of the index entries selected at runtime, see [`AnySuffixArray`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IndexWidth {
+    /// The narrowest of the others holding the values.
+    #[default]
+    Auto,
+    /// [`u32`], up to 4 GiB values.
+    U32,
+    /// [`U40`], up to 1 TiB values.
+    U40,
+    /// [`u64`]
+    U64,
+}
+
+impl IndexWidth {
+    /// Resolve [`Auto`](`IndexWidth::Auto`) to the narrowest width holding `len` values.
+    pub fn for_len(self, len: usize) -> Self {
+        // the builders keep a few entries over the positions.
+        match self {
+            Self::Auto if len <= <u32 as SaIndex>::MAX - 3 => Self::U32,
+            Self::Auto if len <= <U40 as SaIndex>::MAX - 3 => Self::U40,
+            Self::Auto => Self::U64,
+            width => width,
+        }
+    }
+
+    /// The bytes of an entry, or [`None`] for [`Auto`](`IndexWidth::Auto`).
+    pub fn bytes(self) -> Option<usize> {
+        match self {
+            Self::Auto => None,
+            Self::U32 => Some(<u32 as SaIndex>::WIDTH),
+            Self::U40 => Some(<U40 as SaIndex>::WIDTH),
+            Self::U64 => Some(<u64 as SaIndex>::WIDTH),
+        }
+    }
+}
+
+/// [`SuffixArray`] with the [`IndexWidth`] selected at runtime by [`BuildOptions::index_width`].
+#[derive(Debug, Clone)]
+pub enum AnySuffixArray<B, T = u8, M = ()> {
+    U32(SuffixArray<B, T, M, Vec<u32>>),
+    U40(SuffixArray<B, T, M, Vec<U40>>),
+    U64(SuffixArray<B, T, M, Vec<u64>>),
+}
+
+impl<B, T, Im> AnySuffixArray<B, T, Im>
+where
+    T: SelectValue,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    /// Create new [`SuffixArray`] by `options` with the entries of its [`index_width`](`BuildOptions::index_width`).
+    pub fn try_new_with(values: B, mode: Im, options: &BuildOptions) -> Result<Self, BuildError> {
+        Self::try_new_observed_with(values, mode, options, &mut ())
+    }
+
+    /// Create new [`SuffixArray`] like [`try_new_with`](`AnySuffixArray::try_new_with`),
+    /// reporting the progress to `observer`.
+    pub fn try_new_observed_with<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        options: &BuildOptions,
+        observer: &mut O,
+    ) -> Result<Self, BuildError> {
+        match options.index_width.for_len(values.as_ref().len()) {
+            IndexWidth::U32 => options
+                .try_build_observed_with(values, mode, observer)
+                .map(Self::U32),
+            IndexWidth::U40 => options
+                .try_build_observed_with(values, mode, observer)
+                .map(Self::U40),
+            IndexWidth::U64 | IndexWidth::Auto => options
+                .try_build_observed_with(values, mode, observer)
+                .map(Self::U64),
+        }
+    }
+}
+
+impl<B, T, M> AnySuffixArray<B, T, M> {
+    /// The width of the entries.
+    pub fn width(&self) -> IndexWidth {
+        match self {
+            Self::U32(_) => IndexWidth::U32,
+            Self::U40(_) => IndexWidth::U40,
+            Self::U64(_) => IndexWidth::U64,
+        }
+    }
+
+    /// Number of the indices.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U32(sa) => sa.indices.len(),
+            Self::U40(sa) => sa.indices.len(),
+            Self::U64(sa) => sa.indices.len(),
+        }
+    }
+
+    /// Returns true if there is no index.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The text position of the suffix at `rank`.
+    pub fn get(&self, rank: usize) -> Option<usize> {
+        match self {
+            Self::U32(sa) => sa.indices.get(rank).map(|i| i.to_usize()),
+            Self::U40(sa) => sa.indices.get(rank).map(|i| i.to_usize()),
+            Self::U64(sa) => sa.indices.get(rank).map(|i| i.to_usize()),
+        }
+    }
+}
+
+/// Builder configured at runtime, used by [`new_with`](`SuffixArray::new_with`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BuildOptions {
+    algorithm: Algorithm,
+    index_width: IndexWidth,
+    memory_budget: Option<usize>,
+    verify: bool,
+    #[cfg(feature = "parallel")]
+    threads: Option<usize>,
+}
+
+impl BuildOptions {
+    /// Build by `algorithm` without any limit or verification.
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            ..Self::default()
+        }
+    }
+
+    /// Get the algorithm.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Build the entries of `width` by [`AnySuffixArray::try_new_with`].
+    ///
+    /// The other constructors build the entries of their type parameter,
+    /// and fail with [`BuildError::IndexWidth`] if it is not `width`.
+    pub fn index_width(mut self, width: IndexWidth) -> Self {
+        self.index_width = width;
+        self
+    }
+
+    /// Fall back to a leaner algorithm if the [`peak_memory_width`](`Algorithm::peak_memory_width`)
+    /// of the algorithm exceeds `budget` bytes, and fail with [`BuildError::MemoryBudget`] before building
+    /// if even [`Bucket`](`Algorithm::Bucket`) sorting in place does.
+    ///
+    /// Bytes fall back to [`SAISU8`](`Algorithm::SAISU8`) and [`TwoStageU8`](`Algorithm::TwoStageU8`) first,
+    /// as `Bucket` is slow on repetitive values.
+    pub fn memory_budget(mut self, budget: usize) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Check the built indices and fail with [`BuildError::Verification`] if they are wrong.
+    ///
+    /// Compares the adjacent suffixes, which takes long on repetitive values.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Run [`Parallel`](`Algorithm::Parallel`) on a new thread pool of `threads` threads
+    /// instead of the global one, `0` for the default number of rayon.
+    ///
+    /// The other algorithms run on the calling thread.
+    #[cfg(feature = "parallel")]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// The algorithm building `len` values of `T` into the entries of `width` bytes within the budget.
+    pub(crate) fn select<T: 'static>(
+        &self,
+        len: usize,
+        width: usize,
+    ) -> Result<Algorithm, BuildError> {
+        let algorithm = self.algorithm.for_values::<T>();
+        let Some(budget) = self.memory_budget else {
+            return Ok(algorithm);
+        };
+        let fallback: &[Algorithm] = if is_bytes::<T>() {
+            &[Algorithm::SAISU8, Algorithm::TwoStageU8, Algorithm::Bucket]
+        } else {
+            &[Algorithm::Bucket]
+        };
+        std::iter::once(algorithm)
+            .chain(fallback.iter().copied())
+            .find(|a| a.peak_memory_width(len, width) <= budget)
+            .ok_or(BuildError::MemoryBudget {
+                required: Algorithm::Bucket.peak_memory_width(len, width),
+                budget,
+            })
+    }
+}
+
+impl From<Algorithm> for BuildOptions {
+    fn from(algorithm: Algorithm) -> Self {
+        Self::new(algorithm)
+    }
+}
+
+impl<T, B, Im> Builder<B, T, Im> for BuildOptions
+where
+    T: SelectValue,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    /// Build with the default options.
+    #[inline]
+    fn build(values: B, mode: Im) -> SuffixArray<B, T, Im> {
+        Self::default().build_with(values, mode)
+    }
+
+    /// # Panics
+    /// Panics if [`try_build_with`](`Builder::try_build_with`) fails.
+    fn build_with(&self, values: B, mode: Im) -> SuffixArray<B, T, Im> {
+        match self.try_build_with(values, mode) {
+            Ok(sa) => sa,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn try_build_observed_with<X: SaIndex, O: BuildObserver + ?Sized>(
+        &self,
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        if let Some(width) = self.index_width.bytes().filter(|&w| w != X::WIDTH) {
+            return Err(BuildError::IndexWidth {
+                width,
+                entries: X::WIDTH,
+            });
+        }
+        let algorithm = self.select::<T>(values.as_ref().len(), X::WIDTH)?;
+        #[cfg(feature = "parallel")]
+        let pool = match self.threads {
+            Some(threads) if algorithm == Algorithm::Parallel => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| BuildError::ThreadPool(e.to_string()))?,
+            ),
+            _ => None,
+        };
+        let sa = algorithm.try_build_in(
+            values,
+            mode,
+            #[cfg(feature = "parallel")]
+            Threads(pool.as_ref()),
+            observer,
+        )?;
+        if self.verify {
+            verify(sa.values.as_ref(), &sa.indices, &sa.mode)?;
+        }
+        Ok(sa)
+    }
+}
+
+/// Check that `indices` are every position of `values` kept by `mode`, in the suffix order.
+fn verify<T: Ord, Im: IndexMode<T>, X: SaIndex>(
+    values: &[T],
+    indices: &[X],
+    mode: &Im,
+) -> Result<(), BuildError> {
+    for (rank, pos) in indices.iter().enumerate() {
+        let pos = pos.to_usize();
+        let sorted = rank == 0 || values.get(indices[rank - 1].to_usize()..) < values.get(pos..);
+        if pos >= values.len() || !sorted || !mode.is_index(pos, &values[pos]) {
+            return Err(BuildError::Verification { rank });
+        }
+    }
+    let kept = values
+        .iter()
+        .enumerate()
+        .filter(|(i, v)| mode.is_index(*i, v))
+        .count();
+    if kept != indices.len() {
+        return Err(BuildError::Verification {
+            rank: kept.min(indices.len()),
+        });
+    }
+    Ok(())
+}
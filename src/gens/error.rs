@@ -0,0 +1,114 @@
+use bitvec::prelude::*;
+use std::{collections::TryReserveError, fmt};
+
+use super::{observer::Cancelled, IndexMode};
+use crate::indices::SaIndex;
+
+/// Error on building a [`SuffixArray`](`crate::SuffixArray`) by
+/// [`try_new_by`](`crate::SuffixArray::try_new_by`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The memory for the indices could not be allocated.
+    Allocation(TryReserveError),
+    /// The values are too long for the index width.
+    TooLarge { len: usize, width: usize },
+    /// The [`IndexMode`] rejects every position of the non empty values.
+    NoIndexablePosition,
+    /// Every builder needs more working memory than the budget.
+    MemoryBudget { required: usize, budget: usize },
+    /// The built indices are wrong from `rank`, found by [`BuildOptions::verify`](`super::builders::BuildOptions::verify`).
+    Verification { rank: usize },
+    /// The build is cancelled by the [`BuildObserver`](`super::builders::BuildObserver`).
+    Cancelled,
+    /// The [`Algorithm`](`super::builders::Algorithm`) builds only bytes, but the values are not.
+    UnsupportedValues,
+    /// The [`IndexWidth`](`super::builders::IndexWidth`) of `width` bytes is not the one of the entries.
+    IndexWidth { width: usize, entries: usize },
+    /// The thread pool of [`BuildOptions`](`super::builders::BuildOptions`) failed to start.
+    ThreadPool(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allocation(e) => write!(f, "allocation failed: {e}"),
+            Self::TooLarge { len, width } => {
+                write!(f, "length {len} is too large for index width {width}")
+            }
+            Self::NoIndexablePosition => f.write_str("no position is kept by the index mode"),
+            Self::Cancelled => f.write_str("build is cancelled"),
+            Self::Verification { rank } => write!(f, "built indices are wrong at rank {rank}"),
+            Self::MemoryBudget { required, budget } => {
+                write!(f, "{required} bytes are required over the budget {budget}")
+            }
+            Self::UnsupportedValues => f.write_str("the algorithm builds only bytes"),
+            Self::IndexWidth { width, entries } => {
+                write!(f, "index width {width} is not the entries width {entries}")
+            }
+            Self::ThreadPool(e) => write!(f, "thread pool failed to start: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Allocation(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Cancelled> for BuildError {
+    fn from(_: Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+impl From<TryReserveError> for BuildError {
+    fn from(e: TryReserveError) -> Self {
+        Self::Allocation(e)
+    }
+}
+
+/// Check that `values` can be built with `mode` into the indices of `X`.
+pub(crate) fn check_build<T, Im, X>(values: &[T], mode: &Im) -> Result<(), BuildError>
+where
+    Im: IndexMode<T>,
+    X: SaIndex,
+{
+    let len = values.len();
+    // `X::MAX` is kept for the empty slots while building.
+    if len == usize::MAX || len > X::MAX {
+        return Err(BuildError::TooLarge {
+            len,
+            width: X::WIDTH,
+        });
+    }
+    if len > 0 && mode.need_check() && !values.iter().enumerate().any(|(i, v)| mode.is_index(i, v))
+    {
+        return Err(BuildError::NoIndexablePosition);
+    }
+    Ok(())
+}
+
+/// [`Vec::with_capacity`] returning [`BuildError::Allocation`] instead of aborting.
+pub(crate) fn try_with_capacity<V>(capacity: usize) -> Result<Vec<V>, BuildError> {
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(capacity)?;
+    Ok(vec)
+}
+
+/// `vec![value; len]` returning [`BuildError::Allocation`] instead of aborting.
+pub(crate) fn try_vec<V: Clone>(value: V, len: usize) -> Result<Vec<V>, BuildError> {
+    let mut vec = try_with_capacity(len)?;
+    vec.resize(len, value);
+    Ok(vec)
+}
+
+/// Bits of `len` all unset, returning [`BuildError::Allocation`] instead of aborting.
+pub(crate) fn try_bits(len: usize) -> Result<BitVec<usize, Lsb0>, BuildError> {
+    let mut bits = BitVec::from_vec(try_vec(0, len.div_ceil(usize::BITS as usize))?);
+    bits.truncate(len);
+    Ok(bits)
+}
@@ -0,0 +1,124 @@
+use std::mem::size_of;
+
+use super::{
+    builders::{
+        Algorithm, BucketBuilder, BuildError, BuildOptions, NaiveBuilder, SAISBuilder,
+        SAISBuilderU8, SelectValue, TwoStageBuilder, TwoStageBuilderU8,
+    },
+    IndexMode,
+};
+use crate::SuffixArray;
+
+/// Estimated peak working memory of a builder, to schedule builds before starting them.
+///
+/// The estimates are upper bounds in bytes for `len` values, including the built indices
+/// but not the values themselves.
+pub trait PeakMemory {
+    /// Estimated peak memory in bytes to build `len` values into the index entries of `width` bytes,
+    /// e.g. [`SaIndex::WIDTH`](`crate::indices::SaIndex::WIDTH`).
+    fn peak_memory_width(len: usize, width: usize) -> usize;
+
+    /// Estimated peak memory in bytes to build `len` values into `usize` entries.
+    fn peak_memory(len: usize) -> usize {
+        Self::peak_memory_width(len, size_of::<usize>())
+    }
+}
+
+/// `len` entries of `width` bytes, saturating.
+#[inline]
+fn entries(len: usize, width: usize) -> usize {
+    len.saturating_mul(width)
+}
+
+/// Bytes of the type bits of `len` values.
+#[inline]
+fn bits(len: usize) -> usize {
+    len.div_ceil(usize::BITS as usize)
+        .saturating_mul(size_of::<usize>())
+}
+
+/// The indices sorted in place.
+impl PeakMemory for NaiveBuilder {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+    }
+}
+
+/// The indices sorted in place by the first value and then in each bucket.
+impl PeakMemory for BucketBuilder {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+    }
+}
+
+/// The values renamed to at most `len` names, the indices, the type bits and the buckets of the names.
+impl PeakMemory for TwoStageBuilder {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+            .saturating_mul(3)
+            .saturating_add(bits(len))
+    }
+}
+
+/// The indices, the type bits and the 256 buckets.
+impl PeakMemory for TwoStageBuilderU8 {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+            .saturating_add(bits(len))
+            .saturating_add(entries(256, width))
+    }
+}
+
+/// The suffix array holding the next levels, and the type bits and buckets of one level at a time.
+///
+/// The first level has 256 buckets, and the next ones have at most as many names as
+/// their `len / 2` symbols.
+impl PeakMemory for SAISBuilderU8 {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+            .saturating_add(bits(len))
+            .saturating_add(entries((len / 2).max(256), width))
+    }
+}
+
+/// The values renamed to at most `len` names, the suffix array, the type bits and the buckets of the names.
+///
+/// The renaming sorts the positions in place before writing the names.
+impl PeakMemory for SAISBuilder {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        entries(len, width)
+            .saturating_mul(3)
+            .saturating_add(bits(len))
+    }
+}
+
+#[cfg(feature = "parallel")]
+/// The renamed values and at most `3 len + 5` entries on the levels of DC3,
+/// and the partial sums of the chunks.
+impl PeakMemory for super::builders::ParallelBuilder {
+    fn peak_memory_width(len: usize, width: usize) -> usize {
+        // the chunks are at least `1 << 14` long.
+        entries(len.saturating_mul(4).saturating_add(8), width)
+            .saturating_add(entries(len / (1 << 14) + 1, size_of::<usize>()))
+    }
+}
+
+impl<Buf, T, Im> SuffixArray<Buf, T, Im>
+where
+    Buf: AsRef<[T]>,
+    T: SelectValue,
+    Im: IndexMode<T>,
+{
+    /// Create new [`SuffixArray`] within `budget` bytes of working memory.
+    ///
+    /// Builds by [`SAIS`](`Algorithm::SAIS`) with the [`memory_budget`](`BuildOptions::memory_budget`),
+    /// falling back to the leaner builders like it.
+    /// Fails with [`BuildError::MemoryBudget`] if even [`BucketBuilder`] sorting in place doesn't fit.
+    pub fn try_new_with_budget(values: Buf, mode: Im, budget: usize) -> Result<Self, BuildError> {
+        Self::try_new_with(
+            values,
+            mode,
+            &BuildOptions::new(Algorithm::SAIS).memory_budget(budget),
+        )
+    }
+}
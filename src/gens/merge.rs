@@ -0,0 +1,316 @@
+use super::{
+    builders::BuildError,
+    config::as_bytes,
+    error::{try_bits, try_vec, try_with_capacity},
+    observer::unobserved,
+    sais::{rename, rename_bytes, sais},
+    simple::empty_index,
+    IndexMode,
+};
+use crate::{
+    fm::{RankBitsBuilder, WaveletMatrix},
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+use bitvec::prelude::*;
+use std::collections::VecDeque;
+
+impl<T, B, Im> SuffixArray<B, T, Im>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    Im: IndexMode<T> + Clone,
+{
+    /// Merge the arrays of two adjacent texts into the array of `values`,
+    /// which should be the values of `left` followed by the values of `right`.
+    ///
+    /// The suffixes of `right` keep their order, and each suffix of `left` is placed by
+    /// the gap array, the number of the suffixes of `right` smaller than it.
+    /// The gaps are found by the backward search of the suffixes of `left` from the last one
+    /// over the BWT of `right`, in `O(n log σ)` however repetitive the values are.
+    /// The suffixes of `left` keep their order too, except that one being a prefix of others
+    /// is moved after those of them followed by a suffix smaller than `right`,
+    /// found by the lcp of the neighbors in `left`.
+    /// The values are renamed by sorting them, or by the table of the bytes for `u8`.
+    /// The mode is taken from `left`, and should not depend on the positions as they are shifted.
+    ///
+    /// # Panics
+    /// Panics if the length of `values` is not the sum of the lengths of `left` and `right`.
+    pub fn merge<LB, LI, RB, RI>(
+        values: B,
+        left: &SuffixArray<LB, T, Im, LI>,
+        right: &SuffixArray<RB, T, Im, RI>,
+    ) -> Self
+    where
+        T: 'static,
+        LB: AsRef<[T]>,
+        LI: IndexStorage,
+        RB: AsRef<[T]>,
+        RI: IndexStorage,
+    {
+        let text = values.as_ref();
+        let offset = left.values().as_ref().len();
+        let right_len = right.values().as_ref().len();
+        assert_eq!(offset + right_len, text.len());
+        debug_assert!(text[..offset] == *left.values().as_ref());
+        debug_assert!(text[offset..] == *right.values().as_ref());
+
+        let indices = unobserved(|progress| {
+            let bytes = as_bytes(text);
+            let (names, upper) = match bytes {
+                Some(bytes) => rename_bytes::<usize>(bytes)?,
+                None => rename::<T, usize, _>(text, progress)?,
+            };
+            let right_names = &names[offset..];
+
+            // every suffix of `right` in order, and which of them are kept by the mode.
+            let right_kept = right.indices();
+            let right_full = if right_kept.len() == right_len {
+                right_kept.iter().map(|q| q.to_usize()).collect::<Vec<_>>()
+            } else {
+                let mut full = try_vec(empty_index::<usize>(), right_len)?;
+                sais(right_names, &mut full, upper, progress)?;
+                full
+            };
+            let kept = if right_kept.len() == right_len {
+                None
+            } else {
+                let mut positions = try_bits(right_len)?;
+                for q in right_kept.iter() {
+                    positions.set(q.to_usize(), true);
+                }
+                let mut ranks = RankBitsBuilder::new(right_len);
+                for (rank, q) in right_full.iter().enumerate() {
+                    if positions[*q] {
+                        ranks.set(rank);
+                    }
+                }
+                Some(ranks.build())
+            };
+
+            // the BWT of `right` by the rows of its suffixes after the empty one,
+            // without the row `primary` of `right` itself.
+            let mut bwt = try_vec(0, right_len)?;
+            let mut primary = 0;
+            let mut filled = 0;
+            for (row, q) in [right_len].into_iter().chain(right_full).enumerate() {
+                match q {
+                    0 => primary = row,
+                    q => {
+                        bwt[filled] = right_names[q - 1];
+                        filled += 1;
+                    }
+                }
+            }
+            // the first row of each name after the empty suffix.
+            let mut starts = try_vec(0, upper + 2)?;
+            starts[0] = 1;
+            for &name in right_names {
+                starts[name + 1] += 1;
+            }
+            for k in 1..starts.len() {
+                starts[k] += starts[k - 1];
+            }
+            let occ = WaveletMatrix::new(bwt, upper + 1);
+
+            // the rows of the suffixes of `text` from `left` among the suffixes of `right`.
+            let mut gaps = try_vec(0, offset)?;
+            let mut smaller = try_bits(offset)?;
+            let mut row = primary;
+            for p in (0..offset).rev() {
+                let name = names[p];
+                row = starts[name] + occ.rank(name, row - (row > primary) as usize);
+                gaps[p] = row - 1;
+                smaller.set(p, row <= primary);
+            }
+            drop(occ);
+
+            // every suffix of `left` in order, then in the order of `text`.
+            let left_kept = left.indices();
+            let left_order = if left_kept.len() == offset {
+                sort_left(
+                    &text[..offset],
+                    left_kept.iter().map(|p| p.to_usize()),
+                    &smaller,
+                )?
+            } else {
+                let mut full = try_vec(empty_index::<usize>(), offset)?;
+                sais(&names[..offset], &mut full, upper, progress)?;
+                sort_left(&text[..offset], full.iter().copied(), &smaller)?
+            };
+            drop(names);
+
+            let positions = if left_kept.len() == offset {
+                None
+            } else {
+                let mut positions = try_bits(offset)?;
+                for p in left_kept.iter() {
+                    positions.set(p.to_usize(), true);
+                }
+                Some(positions)
+            };
+            let gap = |p: usize| match &kept {
+                Some(ranks) => ranks.rank(gaps[p]),
+                None => gaps[p],
+            };
+
+            // the gaps don't decrease along the order of `left`.
+            let mut indices = try_with_capacity(left_kept.len() + right_kept.len())?;
+            let mut left_order = left_order
+                .into_iter()
+                .filter(|&p| positions.as_ref().is_none_or(|positions| positions[p]))
+                .peekable();
+            for (rank, q) in right_kept.iter().enumerate() {
+                while let Some(p) = left_order.next_if(|&p| gap(p) == rank) {
+                    indices.push(p);
+                }
+                indices.push(offset + q.to_usize());
+            }
+            indices.extend(left_order);
+            Ok(indices)
+        });
+        SuffixArray::from_parts(values, indices, left.mode().clone())
+    }
+}
+
+/// Suffixes of `left` repeated in it longer than this are found by the lcp of all the neighbors.
+const DIRECT_LCP: usize = 256;
+
+/// Sort the suffixes of `text` from `left`, given in the order of `left`,
+/// where `smaller` tells which of them are smaller than the rest of `text`.
+///
+/// They keep the order of `left` unless one is a prefix of the other. Such a suffix waits
+/// until its block of longer ones ends or one of them followed by a suffix not smaller
+/// than the rest comes, in `waiting` sorted by the order of `text`.
+fn sort_left<T: Eq>(
+    left: &[T],
+    order: impl Iterator<Item = usize> + Clone,
+    smaller: &BitSlice,
+) -> Result<Vec<usize>, BuildError> {
+    let n = left.len();
+    let repeated = repeated_suffix(left, DIRECT_LCP);
+    let next_lcp = match repeated {
+        DIRECT_LCP => Some(next_lcp(left, order.clone())?),
+        _ => None,
+    };
+    let mut sorted = try_with_capacity(n)?;
+    let mut waiting = VecDeque::new();
+    // the blocks around the current suffix, innermost last.
+    let mut nested = Vec::<usize>::new();
+    let mut placed = try_bits(n)?;
+    let mut ended = try_bits(n)?;
+    let mut ended_count = 0;
+    let mut lcp = 0;
+    for p in order {
+        while let Some(&q) = nested.last() {
+            let inside = match next_lcp {
+                Some(_) => n - q <= lcp,
+                None => left[p..].starts_with(&left[q..]),
+            };
+            if inside {
+                break;
+            }
+            nested.pop();
+            if !placed[q] {
+                ended.set(q, true);
+                ended_count += 1;
+            }
+        }
+        // the waiting ones smaller than an ended one are smaller than the rest.
+        while ended_count > 0 {
+            let q = waiting.pop_front().unwrap();
+            placed.set(q, true);
+            ended_count -= ended[q] as usize;
+            sorted.push(q);
+        }
+        let before = |q: &usize| !smaller[p + n - q];
+        let prefix = match &next_lcp {
+            Some(next_lcp) => {
+                lcp = next_lcp[p];
+                lcp == n - p
+            }
+            None => p >= n - repeated,
+        };
+        if prefix {
+            let at = match (waiting.front(), waiting.back()) {
+                (Some(q), _) if !before(q) => 0,
+                (_, Some(q)) if !before(q) => waiting.partition_point(before),
+                _ => waiting.len(),
+            };
+            waiting.insert(at, p);
+            nested.push(p);
+        } else {
+            while let Some(q) = waiting.front() {
+                if !before(q) {
+                    break;
+                }
+                placed.set(*q, true);
+                sorted.extend(waiting.pop_front());
+            }
+            sorted.push(p);
+        }
+    }
+    sorted.extend(waiting);
+    Ok(sorted)
+}
+
+/// Length of the longest suffix of `values` found elsewhere in it, up to `limit`,
+/// by KMP of the suffix of `limit` reversed over the values reversed.
+fn repeated_suffix<T: Eq>(values: &[T], limit: usize) -> usize {
+    let n = values.len();
+    let m = limit.min(n.saturating_sub(1));
+    if m == 0 {
+        return 0;
+    }
+    let pattern = |i: usize| &values[n - 1 - i];
+    // the longest border of each prefix of the pattern.
+    let mut borders = vec![0; m];
+    let mut k = 0;
+    for i in 1..m {
+        while k > 0 && pattern(i) != pattern(k) {
+            k = borders[k - 1];
+        }
+        k += (pattern(i) == pattern(k)) as usize;
+        borders[i] = k;
+    }
+    let mut longest = 0;
+    k = 0;
+    for v in values[..n - 1].iter().rev() {
+        while k > 0 && v != pattern(k) {
+            k = borders[k - 1];
+        }
+        k += (v == pattern(k)) as usize;
+        longest = longest.max(k);
+        if longest == m {
+            break;
+        }
+    }
+    longest
+}
+
+/// The lcp of each suffix of `values` with the next one in `order` by the Φ algorithm.
+fn next_lcp<T: Eq>(
+    values: &[T],
+    order: impl Iterator<Item = usize> + Clone,
+) -> Result<Vec<usize>, BuildError> {
+    let mut lcp = try_vec(empty_index::<usize>(), values.len())?;
+    for (p, q) in order.clone().zip(order.skip(1)) {
+        lcp[p] = q;
+    }
+    let mut h = 0;
+    for p in 0..values.len() {
+        let q = lcp[p];
+        h = if q == empty_index::<usize>() {
+            0
+        } else {
+            h + values[p + h..]
+                .iter()
+                .zip(&values[q + h..])
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+        lcp[p] = h;
+        h = h.saturating_sub(1);
+    }
+    Ok(lcp)
+}
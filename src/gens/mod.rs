@@ -1,7 +1,14 @@
 //! module that provides some options to build [`SuffixArray`](`crate::SuffixArray`),
 //! at [`new`](`crate::SuffixArray::new`).
 
+mod config;
+mod error;
+mod memory;
+mod merge;
 pub mod modes;
+mod observer;
+#[cfg(feature = "parallel")]
+mod parallel;
 mod sais;
 mod simple;
 mod two_stage;
@@ -32,16 +39,192 @@ pub mod builders {
         {
             B::build(values, mode)
         }
+
+        /// Create new [`SuffixArray`] by the instance of [`Builder`], e.g. [`BuildOptions`].
+        pub fn new_with<B>(values: Buf, mode: Im, builder: &B) -> Self
+        where
+            B: Builder<Buf, T, Im>,
+        {
+            builder.build_with(values, mode)
+        }
+    }
+
+    impl<Buf, T, Im, X> SuffixArray<Buf, T, Im, Vec<X>>
+    where
+        Buf: std::convert::AsRef<[T]>,
+        T: std::cmp::Ord,
+        X: SaIndex,
+    {
+        /// Create new [`SuffixArray`] with the index entries of `X` by the [`Builder`].
+        ///
+        /// The builders of this crate write the entries of `X` directly,
+        /// so a narrow `X` also saves the memory while building.
+        ///
+        /// # Panics
+        /// Panics if the values are too long for `X`.
+        pub fn new_by_width<B>(values: Buf, mode: Im) -> Self
+        where
+            B: Builder<Buf, T, Im>,
+            Im: super::IndexMode<T>,
+        {
+            match B::try_build_observed(values, mode, &mut ()) {
+                Ok(sa) => sa,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    impl<Buf, T, Im> SuffixArray<Buf, T, Im>
+    where
+        Buf: std::convert::AsRef<[T]>,
+        T: std::cmp::Ord,
+        Im: super::IndexMode<T>,
+    {
+        /// Create new [`SuffixArray`] by the [`Builder`], returning [`BuildError`] instead of panicking.
+        pub fn try_new_by<B>(values: Buf, mode: Im) -> Result<Self, BuildError>
+        where
+            B: Builder<Buf, T, Im>,
+        {
+            B::try_build(values, mode)
+        }
+
+        /// Create new [`SuffixArray`] by the [`Builder`], reporting the progress to `observer`.
+        ///
+        /// Returns [`BuildError::Cancelled`] if `observer` breaks.
+        pub fn try_new_observed<B, O>(
+            values: Buf,
+            mode: Im,
+            observer: &mut O,
+        ) -> Result<Self, BuildError>
+        where
+            B: Builder<Buf, T, Im>,
+            O: BuildObserver + ?Sized,
+        {
+            B::try_build_observed(values, mode, observer)
+        }
+
+        /// Create new [`SuffixArray`] by the instance of [`Builder`], returning [`BuildError`] instead of panicking.
+        pub fn try_new_with<B>(values: Buf, mode: Im, builder: &B) -> Result<Self, BuildError>
+        where
+            B: Builder<Buf, T, Im>,
+        {
+            builder.try_build_with(values, mode)
+        }
+    }
+
+    impl<Buf, T, Im, X> SuffixArray<Buf, T, Im, Vec<X>>
+    where
+        Buf: std::convert::AsRef<[T]>,
+        T: std::cmp::Ord,
+        Im: super::IndexMode<T>,
+        X: SaIndex,
+    {
+        /// Create new [`SuffixArray`] with the index entries of `X` by the [`Builder`],
+        /// returning [`BuildError`] if the values are too long for `X`.
+        pub fn try_new_by_width<B>(values: Buf, mode: Im) -> Result<Self, BuildError>
+        where
+            B: Builder<Buf, T, Im>,
+        {
+            B::try_build_observed(values, mode, &mut ())
+        }
+
+        /// Create new [`SuffixArray`] with the index entries of `X` by the instance of [`Builder`],
+        /// returning [`BuildError`] if the values are too long for `X`.
+        pub fn try_new_with_width<B>(values: Buf, mode: Im, builder: &B) -> Result<Self, BuildError>
+        where
+            B: Builder<Buf, T, Im>,
+        {
+            builder.try_build_observed_with(values, mode, &mut ())
+        }
     }
 
     /// Build a SuffixArray
     pub trait Builder<B, T, Im> {
         /// Create a new [`SuffixArray`]
         fn build(values: B, mode: Im) -> SuffixArray<B, T, Im>;
+
+        /// Create a new [`SuffixArray`], checking the length, the mode and the allocation first.
+        fn try_build(values: B, mode: Im) -> Result<SuffixArray<B, T, Im>, BuildError>
+        where
+            B: AsRef<[T]>,
+            T: Ord,
+            Im: super::IndexMode<T>,
+        {
+            Self::try_build_observed(values, mode, &mut ())
+        }
+
+        /// Create a new [`SuffixArray`] with the index entries of `X` like [`try_build`](`Builder::try_build`),
+        /// reporting the progress to `observer` which can cancel the build.
+        ///
+        /// The default implementation reports nothing and narrows the indices after [`build`](`Builder::build`).
+        /// Builders of this crate report their phases and write the entries of `X` directly.
+        fn try_build_observed<X, O>(
+            values: B,
+            mode: Im,
+            observer: &mut O,
+        ) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError>
+        where
+            B: AsRef<[T]>,
+            T: Ord,
+            Im: super::IndexMode<T>,
+            X: SaIndex,
+            O: BuildObserver + ?Sized,
+        {
+            let _ = observer;
+            super::error::check_build::<T, Im, X>(values.as_ref(), &mode)?;
+            match Self::build(values, mode).with_index_width() {
+                Ok(sa) => Ok(sa),
+                Err(_) => unreachable!(),
+            }
+        }
+
+        /// Create a new [`SuffixArray`] by this instance.
+        ///
+        /// The zero sized builders call [`build`](`Builder::build`),
+        /// and the configurable ones like [`BuildOptions`] use their options.
+        fn build_with(&self, values: B, mode: Im) -> SuffixArray<B, T, Im> {
+            Self::build(values, mode)
+        }
+
+        /// Create a new [`SuffixArray`] by this instance like [`try_build`](`Builder::try_build`).
+        fn try_build_with(&self, values: B, mode: Im) -> Result<SuffixArray<B, T, Im>, BuildError>
+        where
+            B: AsRef<[T]>,
+            T: Ord,
+            Im: super::IndexMode<T>,
+        {
+            self.try_build_observed_with(values, mode, &mut ())
+        }
+
+        /// Create a new [`SuffixArray`] by this instance like [`try_build_observed`](`Builder::try_build_observed`).
+        fn try_build_observed_with<X, O>(
+            &self,
+            values: B,
+            mode: Im,
+            observer: &mut O,
+        ) -> Result<SuffixArray<B, T, Im, Vec<X>>, BuildError>
+        where
+            B: AsRef<[T]>,
+            T: Ord,
+            Im: super::IndexMode<T>,
+            X: SaIndex,
+            O: BuildObserver + ?Sized,
+        {
+            Self::try_build_observed(values, mode, observer)
+        }
     }
 
-    use crate::SuffixArray;
+    use crate::{indices::SaIndex, SuffixArray};
+
+    pub use super::config::{
+        Algorithm, AnySuffixArray, BuildOptions, IndexWidth, ParseAlgorithmError, SelectValue,
+    };
+    pub use super::error::BuildError;
+    pub use super::memory::PeakMemory;
+    pub use super::observer::{BuildObserver, Phase};
 
+    #[cfg(feature = "parallel")]
+    pub use super::parallel::*;
     pub use super::sais::*;
     pub use super::simple::*;
     pub use super::two_stage::*;
@@ -0,0 +1,301 @@
+use std::{cell::Cell, cmp::Ordering, ops::ControlFlow};
+
+use super::builders::BuildError;
+
+/// Phase of a build reported to a [`BuildObserver`].
+///
+/// Builders report only the phases they have, and SA-IS reports its phases again
+/// on each recursion level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Renaming the values, or the LMS substrings of SA-IS, to integers keeping their order.
+    Rename,
+    /// Classifying the suffixes into L and S types.
+    Classification,
+    /// Filling the positions into the buckets of their first value.
+    BucketFill,
+    /// Sorting the suffixes by comparing them.
+    Sort,
+    /// Inducing the L type suffixes.
+    LInduction,
+    /// Inducing the S type suffixes.
+    SInduction,
+    /// Removing the positions rejected by the [`IndexMode`](`super::IndexMode`).
+    CheckRemoveIndex,
+}
+
+/// Observer of a build, called with the phase and the progress fraction in `0.0..=1.0`.
+///
+/// Returning [`ControlFlow::Break`] aborts the build,
+/// and [`try_new_observed`](`crate::SuffixArray::try_new_observed`) returns
+/// [`BuildError::Cancelled`](`super::builders::BuildError::Cancelled`).
+pub trait BuildObserver {
+    /// Called at the start and end of each phase, and periodically in it.
+    fn progress(&mut self, phase: Phase, fraction: f64) -> ControlFlow<()>;
+}
+
+/// Observer ignoring the progress.
+impl BuildObserver for () {
+    #[inline]
+    fn progress(&mut self, _phase: Phase, _fraction: f64) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+impl<F> BuildObserver for F
+where
+    F: FnMut(Phase, f64) -> ControlFlow<()>,
+{
+    #[inline]
+    fn progress(&mut self, phase: Phase, fraction: f64) -> ControlFlow<()> {
+        self(phase, fraction)
+    }
+}
+
+/// The build is cancelled by the observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cancelled;
+
+/// Steps between the reports in a phase.
+const REPORT_INTERVAL: usize = 1 << 16;
+
+/// Estimated comparisons to sort `len` items, the steps of a sort.
+#[inline]
+pub(crate) fn comparisons(len: usize) -> usize {
+    len.saturating_mul((usize::BITS - len.leading_zeros()) as usize)
+}
+
+/// Progress of a phase, reporting to the observer every [`REPORT_INTERVAL`] steps.
+pub(crate) struct Progress<'a, O: ?Sized> {
+    observer: &'a mut O,
+    phase: Phase,
+    total: usize,
+    next: usize,
+}
+
+impl<'a, O: BuildObserver + ?Sized> Progress<'a, O> {
+    pub(crate) fn new(observer: &'a mut O) -> Self {
+        Self {
+            observer,
+            phase: Phase::Classification,
+            total: 0,
+            next: usize::MAX,
+        }
+    }
+
+    fn report(&mut self, fraction: f64) -> Result<(), Cancelled> {
+        match self.observer.progress(self.phase, fraction) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(()) => Err(Cancelled),
+        }
+    }
+
+    /// Start `phase` of `total` steps.
+    pub(crate) fn start(&mut self, phase: Phase, total: usize) -> Result<(), Cancelled> {
+        self.phase = phase;
+        self.total = total;
+        self.next = REPORT_INTERVAL;
+        self.report(0.0)
+    }
+
+    fn fraction(&self, done: usize) -> f64 {
+        (done as f64 / self.total.max(1) as f64).min(1.0)
+    }
+
+    /// `done` steps of the current phase are done.
+    #[inline]
+    pub(crate) fn step(&mut self, done: usize) -> Result<(), Cancelled> {
+        if done < self.next {
+            return Ok(());
+        }
+        self.next = done.saturating_add(REPORT_INTERVAL);
+        self.report(self.fraction(done))
+    }
+
+    /// Sort `v` by `compare` returning the ordering and its cost, counting a step per comparison from `done`.
+    ///
+    /// The observer is called every [`REPORT_INTERVAL`] of the cost, so a sort of long equal runs
+    /// can be cancelled too. The comparisons stay consistent after the cancel, as the sort of std
+    /// may panic otherwise, and [`quicksort`] stops at the next partition or slice.
+    pub(crate) fn sort_by<V>(
+        &mut self,
+        v: &mut [V],
+        done: &mut usize,
+        mut compare: impl FnMut(&V, &V) -> (Ordering, usize),
+    ) -> Result<(), Cancelled> {
+        let cancelled = Cell::new(false);
+        let mut cost = 0;
+        let mut compare = |a: &V, b: &V| {
+            let (ordering, c) = compare(a, b);
+            *done += 1;
+            cost += c;
+            if cost >= REPORT_INTERVAL && !cancelled.get() {
+                cost = 0;
+                cancelled.set(self.report(self.fraction(*done)).is_err());
+            }
+            ordering
+        };
+        let limit = 2 * (usize::BITS - v.len().leading_zeros());
+        quicksort(v, None, limit, &mut compare, &cancelled);
+        match cancelled.get() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+
+    /// Finish the current phase.
+    pub(crate) fn finish(&mut self) -> Result<(), Cancelled> {
+        self.next = usize::MAX;
+        self.report(1.0)
+    }
+}
+
+/// Slices sorted by the sort of std in [`quicksort`].
+const SMALL_LEN: usize = 1 << 10;
+/// Items compared at once in [`partition`].
+const BLOCK_LEN: usize = 64;
+
+/// Introsort partitioning around the median of three, sorting the slices up to [`SMALL_LEN`]
+/// by std and falling back to [`heapsort`] after `limit` levels.
+///
+/// `pred` is an item before `v` not larger than any in it, and the items equal to it
+/// are split off at once, so long runs of equal items take linear time.
+/// Returns at the next partition or slice once `cancelled` is set, leaving `v` partly sorted.
+fn quicksort<'a, V>(
+    mut v: &'a mut [V],
+    mut pred: Option<&'a V>,
+    mut limit: u32,
+    compare: &mut impl FnMut(&V, &V) -> Ordering,
+    cancelled: &Cell<bool>,
+) {
+    loop {
+        let len = v.len();
+        if cancelled.get() {
+            return;
+        }
+        if len <= SMALL_LEN {
+            return v.sort_unstable_by(|a, b| compare(a, b));
+        }
+        if limit == 0 {
+            return heapsort(v, compare, cancelled);
+        }
+        limit -= 1;
+        let pivot = median_of_three(v, compare);
+        v.swap(0, pivot);
+        let equal_pred = pred.is_some_and(|pred| compare(pred, &v[0]) != Ordering::Less);
+        let (pivot, rest) = v.split_first_mut().unwrap();
+        if equal_pred {
+            let equal = partition(rest, pivot, |o| o != Ordering::Greater, compare, cancelled);
+            v = &mut std::mem::take(&mut v)[equal + 1..];
+            continue;
+        }
+        let mid = partition(rest, pivot, |o| o == Ordering::Less, compare, cancelled);
+        v.swap(0, mid);
+        let (less, rest) = std::mem::take(&mut v).split_at_mut(mid);
+        let (pivot, greater) = rest.split_first_mut().unwrap();
+        // recurse into the shorter one to bound the stack.
+        let pivot = &*pivot;
+        if less.len() < greater.len() {
+            quicksort(less, pred, limit, compare, cancelled);
+            (v, pred) = (greater, Some(pivot));
+        } else {
+            quicksort(greater, Some(pivot), limit, compare, cancelled);
+            v = less;
+        }
+    }
+}
+
+/// Move the items of `v` going `left` by their ordering to `pivot` before the others,
+/// and return their number.
+///
+/// The items of a block are compared before moving any, so the comparisons don't wait
+/// for each other.
+fn partition<V>(
+    v: &mut [V],
+    pivot: &V,
+    left: impl Fn(Ordering) -> bool,
+    compare: &mut impl FnMut(&V, &V) -> Ordering,
+    cancelled: &Cell<bool>,
+) -> usize {
+    let mut lt = 0;
+    let mut flags = [false; BLOCK_LEN];
+    for start in (0..v.len()).step_by(BLOCK_LEN) {
+        let block = start..v.len().min(start + BLOCK_LEN);
+        for (flag, item) in flags.iter_mut().zip(&v[block.clone()]) {
+            *flag = left(compare(item, pivot));
+        }
+        for (k, flag) in block.zip(flags) {
+            v.swap(lt, k);
+            lt += flag as usize;
+        }
+        if cancelled.get() {
+            // the caller returns before sorting the parts.
+            return lt;
+        }
+    }
+    lt
+}
+
+/// Index of the median of the first, middle and last items.
+fn median_of_three<V>(v: &[V], compare: &mut impl FnMut(&V, &V) -> Ordering) -> usize {
+    let (a, b, c) = (0, v.len() / 2, v.len() - 1);
+    let mut less = |x: usize, y: usize| compare(&v[x], &v[y]) == Ordering::Less;
+    if less(a, b) {
+        if less(b, c) {
+            b
+        } else if less(a, c) {
+            c
+        } else {
+            a
+        }
+    } else if less(a, c) {
+        a
+    } else if less(b, c) {
+        c
+    } else {
+        b
+    }
+}
+
+fn heapsort<V>(v: &mut [V], compare: &mut impl FnMut(&V, &V) -> Ordering, cancelled: &Cell<bool>) {
+    for node in (0..v.len() / 2).rev() {
+        sift_down(v, node, compare);
+    }
+    for end in (1..v.len()).rev() {
+        if cancelled.get() {
+            return;
+        }
+        v.swap(0, end);
+        sift_down(&mut v[..end], 0, compare);
+    }
+}
+
+/// Move `v[node]` down the max heap `v`.
+fn sift_down<V>(v: &mut [V], mut node: usize, compare: &mut impl FnMut(&V, &V) -> Ordering) {
+    loop {
+        let mut child = 2 * node + 1;
+        if child >= v.len() {
+            return;
+        }
+        if child + 1 < v.len() && compare(&v[child], &v[child + 1]) == Ordering::Less {
+            child += 1;
+        }
+        if compare(&v[node], &v[child]) != Ordering::Less {
+            return;
+        }
+        v.swap(node, child);
+        node = child;
+    }
+}
+
+/// Run `f` without observing, which never cancels.
+///
+/// # Panics
+/// Panics if `f` fails, e.g. on the allocation.
+#[inline]
+pub(crate) fn unobserved<R>(f: impl FnOnce(&mut Progress<'_, ()>) -> Result<R, BuildError>) -> R {
+    match f(&mut Progress::new(&mut ())) {
+        Ok(r) => r,
+        Err(e) => panic!("{e}"),
+    }
+}
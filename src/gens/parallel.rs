@@ -0,0 +1,355 @@
+use super::{
+    builders::{BuildError, Builder},
+    error::{check_build, try_vec, try_with_capacity},
+    observer::{unobserved, BuildObserver, Phase, Progress},
+    simple::to_index,
+    IndexMode,
+};
+use crate::{indices::SaIndex, SuffixArray};
+use rayon::{prelude::*, ThreadPool};
+use std::marker::PhantomData;
+
+/// Builder running the DC3 (skew) algorithm of Kärkkäinen and Sanders on the global rayon thread pool.
+///
+/// Each recursion level sorts integer keys and merges in parallel,
+/// so the work is `O(n log n)` however repetitive the values are.
+///
+/// Available with the `parallel` feature.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ParallelBuilder;
+
+impl<B, T, Im> Builder<B, T, Im> for ParallelBuilder
+where
+    T: Ord + Sync,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    #[inline]
+    fn build(values: B, mode: Im) -> crate::SuffixArray<B, T, Im> {
+        SuffixArray::new_parallel(values, mode)
+    }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        SuffixArray::try_new_parallel(values, mode, Threads::default(), observer)
+    }
+}
+
+/// Levels shorter than this are sorted by comparing the suffixes.
+const SMALL_LEN: usize = 64;
+
+impl<T, B, Im> SuffixArray<B, T, Im>
+where
+    T: Ord + Sync,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    pub(crate) fn new_parallel(values: B, mode: Im) -> Self {
+        unobserved(|progress| {
+            Self::new_parallel_observed(values, mode, Threads::default(), progress)
+        })
+    }
+}
+
+impl<T, B, Im, X> SuffixArray<B, T, Im, Vec<X>>
+where
+    T: Ord + Sync,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+    X: SaIndex,
+{
+    /// Check the values and build on `threads`.
+    pub(crate) fn try_new_parallel<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        threads: Threads<'_>,
+        observer: &mut O,
+    ) -> Result<Self, BuildError> {
+        let len = values.as_ref().len();
+        check_build::<T, Im, X>(values.as_ref(), &mode)?;
+        // the text is padded by three positions.
+        if len > X::MAX - 3 {
+            return Err(BuildError::TooLarge {
+                len,
+                width: X::WIDTH,
+            });
+        }
+        Self::new_parallel_observed(values, mode, threads, &mut Progress::new(observer))
+    }
+
+    /// The steps run on `threads`, so the progress is reported between them.
+    pub(crate) fn new_parallel_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        threads: Threads<'_>,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
+        let source = values.as_ref();
+        let n = source.len();
+        progress.start(Phase::Rename, 1)?;
+        let text = rename::<T, X>(source, threads)?;
+        progress.finish()?;
+        // every level is at most 2/3 of the previous one.
+        progress.start(Phase::Sort, n.saturating_mul(3))?;
+        let mut done = 0;
+        let mut indices = dc3(&text, threads, progress, &mut done)?;
+        drop(text);
+        progress.finish()?;
+        Self::gen_check(source, &indices);
+        Self::check_remove_index(source, &mut indices, &mode, progress)?;
+        Ok(Self {
+            values,
+            indices,
+            mode,
+            value_type: PhantomData,
+        })
+    }
+}
+
+/// Thread pool running the steps, or the global one.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Threads<'a>(pub(crate) Option<&'a ThreadPool>);
+
+impl Threads<'_> {
+    fn run<R: Send>(self, op: impl FnOnce() -> R + Send) -> R {
+        match self.0 {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+}
+
+/// Length of the chunks processed by a thread.
+fn chunk_len(len: usize) -> usize {
+    len.div_ceil(rayon::current_num_threads() * 8).max(1 << 14)
+}
+
+/// Inclusive prefix sums of `v`, returns the total.
+fn prefix_sum<X: SaIndex>(v: &mut [X]) -> usize {
+    let chunk = chunk_len(v.len());
+    let mut offsets = v
+        .par_chunks(chunk)
+        .map(|c| c.iter().map(|x| x.to_usize()).sum::<usize>())
+        .collect::<Vec<_>>();
+    let mut total = 0;
+    for offset in offsets.iter_mut() {
+        let sum = *offset;
+        *offset = total;
+        total += sum;
+    }
+    v.par_chunks_mut(chunk)
+        .zip(offsets)
+        .for_each(|(c, mut sum)| {
+            for x in c {
+                sum += x.to_usize();
+                *x = to_index(sum);
+            }
+        });
+    total
+}
+
+/// Slice written from the threads at distinct positions.
+struct Scatter<X>(*mut X, usize);
+
+// SAFETY: the users write distinct positions, see `scatter`.
+unsafe impl<X: Send> Sync for Scatter<X> {}
+
+impl<X> Scatter<X> {
+    /// # Safety
+    /// No other thread writes `i`.
+    #[inline]
+    unsafe fn write(&self, i: usize, x: X) {
+        assert!(i < self.1);
+        self.0.add(i).write(x)
+    }
+}
+
+/// Write `items` into `dst` at their positions.
+///
+/// # Safety
+/// The positions of `items` must be distinct.
+unsafe fn scatter<X: SaIndex>(dst: &mut [X], items: impl ParallelIterator<Item = (usize, X)>) {
+    let dst = Scatter(dst.as_mut_ptr(), dst.len());
+    items.for_each(|(i, x)| dst.write(i, x));
+}
+
+/// Rename `values` to the integer alphabet `1..=sigma` keeping the order, padded by three zeros.
+fn rename<T: Ord + Sync, X: SaIndex>(
+    values: &[T],
+    threads: Threads<'_>,
+) -> Result<Vec<X>, BuildError> {
+    let n = values.len();
+    let mut order = try_with_capacity::<X>(n)?;
+    let mut names = try_with_capacity::<X>(n)?;
+    let mut text = try_vec(to_index::<X>(0), n + 3)?;
+    threads.run(|| {
+        order.par_extend((0..n).into_par_iter().map(to_index::<X>));
+        order.par_sort_unstable_by_key(|p| &values[p.to_usize()]);
+        names.par_extend((0..n).into_par_iter().map(|k| {
+            let differs = k == 0 || values[order[k - 1].to_usize()] != values[order[k].to_usize()];
+            to_index::<X>(differs as usize)
+        }));
+        prefix_sum(&mut names);
+        // SAFETY: `order` is a permutation.
+        unsafe {
+            scatter(
+                &mut text,
+                order
+                    .par_iter()
+                    .zip(&names)
+                    .map(|(p, &name)| (p.to_usize(), name)),
+            );
+        }
+    });
+    Ok(text)
+}
+
+/// DC3 (skew) algorithm, returns the suffix array of `t` without the padding.
+///
+/// The symbols of `t` are positive and `t` is padded by three zeros.
+/// The positions not divisible by 3 are sorted by their triples, and recursively if the triples
+/// are not unique. Then the positions divisible by 3 are sorted by their first symbol
+/// and the rank of the next position, and both are merged.
+///
+/// A level of `n` symbols holds at most `3n` entries besides `t` including the next levels,
+/// as the next level is at most `2n / 3`.
+fn dc3<X: SaIndex, O: BuildObserver + ?Sized>(
+    t: &[X],
+    threads: Threads<'_>,
+    progress: &mut Progress<'_, O>,
+    done: &mut usize,
+) -> Result<Vec<X>, BuildError> {
+    let n = t.len() - 3;
+    if n <= SMALL_LEN {
+        let mut sa = try_with_capacity(n)?;
+        sa.extend((0..n).map(to_index::<X>));
+        sa.sort_unstable_by(|a, b| t[a.to_usize()..n].cmp(&t[b.to_usize()..n]));
+        return Ok(sa);
+    }
+    // the positions `1 mod 3` take the slots `0..n0` and `2 mod 3` take `n0..n02`,
+    // with the dummy position `n` if `n % 3 == 1`.
+    let (n0, n1, n2) = (n.div_ceil(3), (n + 1) / 3, n / 3);
+    let n02 = n0 + n2;
+    let position = move |k: usize| {
+        if k < n0 {
+            3 * k + 1
+        } else {
+            3 * (k - n0) + 2
+        }
+    };
+    let slot = move |p: usize| {
+        if p % 3 == 1 {
+            p / 3
+        } else {
+            p / 3 + n0
+        }
+    };
+    let triple = |p: X| {
+        let p = p.to_usize();
+        (t[p], t[p + 1], t[p + 2])
+    };
+
+    // the names of the triples in the slot order, ranks after sorting.
+    let mut s12 = try_with_capacity::<X>(n02)?;
+    let mut names = try_with_capacity::<X>(n02)?;
+    let mut ranks = try_vec(to_index::<X>(0), n02 + 3)?;
+    let upper = threads.run(|| {
+        s12.par_extend((0..n02).into_par_iter().map(|k| to_index::<X>(position(k))));
+        s12.par_sort_unstable_by_key(|&p| triple(p));
+        names.par_extend((0..n02).into_par_iter().map(|k| {
+            let differs = k == 0 || triple(s12[k - 1]) != triple(s12[k]);
+            to_index::<X>(differs as usize)
+        }));
+        let upper = prefix_sum(&mut names);
+        // SAFETY: the slots of the distinct positions are distinct.
+        unsafe {
+            scatter(
+                &mut ranks,
+                s12.par_iter()
+                    .zip(&names)
+                    .map(|(p, &name)| (slot(p.to_usize()), name)),
+            );
+        }
+        upper
+    });
+    drop(names);
+    *done += n02;
+    progress.step(*done)?;
+    let sa12 = if upper < n02 {
+        drop(s12);
+        let sa12 = dc3(&ranks, threads, progress, done)?;
+        threads.run(|| {
+            // SAFETY: `sa12` is a permutation of the slots.
+            unsafe {
+                scatter(
+                    &mut ranks,
+                    sa12.par_iter()
+                        .enumerate()
+                        .map(|(i, &k)| (k.to_usize(), to_index(i + 1))),
+                );
+            }
+        });
+        sa12
+    } else {
+        threads.run(|| {
+            s12.par_iter_mut()
+                .for_each(|p| *p = to_index(slot(p.to_usize())))
+        });
+        s12
+    };
+    let rank = |p: usize| ranks[slot(p)];
+
+    let mut sa0 = try_with_capacity::<X>(n0)?;
+    threads.run(|| {
+        sa0.par_extend((0..n0).into_par_iter().map(|k| to_index::<X>(3 * k)));
+        sa0.par_sort_unstable_by_key(|j| {
+            let j = j.to_usize();
+            (t[j], rank(j + 1))
+        });
+    });
+    *done += n02 + n0;
+    progress.step(*done)?;
+
+    // merge, each chunk of the output finds its start in both by binary search.
+    let sa12 = &sa12[n0 - n1..];
+    let less = |k: X, j: X| {
+        let (i, j) = (position(k.to_usize()), j.to_usize());
+        if i % 3 == 1 {
+            (t[i], rank(i + 1)) < (t[j], rank(j + 1))
+        } else {
+            (t[i], t[i + 1], rank(i + 2)) < (t[j], t[j + 1], rank(j + 2))
+        }
+    };
+    let mut sa = try_vec(to_index::<X>(0), n)?;
+    threads.run(|| {
+        let chunk = chunk_len(n);
+        sa.par_chunks_mut(chunk).enumerate().for_each(|(c, out)| {
+            let start = c * chunk;
+            let (mut lo, mut hi) = (start.saturating_sub(sa0.len()), start.min(sa12.len()));
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if less(sa12[mid], sa0[start - mid - 1]) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            let (mut i, mut j) = (lo, start - lo);
+            for o in out {
+                if j == sa0.len() || (i < sa12.len() && less(sa12[i], sa0[j])) {
+                    *o = to_index(position(sa12[i].to_usize()));
+                    i += 1;
+                } else {
+                    *o = sa0[j];
+                    j += 1;
+                }
+            }
+        });
+    });
+    *done += n;
+    progress.step(*done)?;
+    Ok(sa)
+}
@@ -1,7 +1,13 @@
-use super::{builders::Builder, IndexMode};
-use crate::SuffixArray;
+use super::{
+    builders::{BuildError, Builder},
+    error::{check_build, try_bits, try_vec, try_with_capacity},
+    observer::{comparisons, unobserved, BuildObserver, Phase, Progress},
+    simple::{empty_index, to_index},
+    IndexMode,
+};
+use crate::{indices::SaIndex, SuffixArray};
 use bitvec::prelude::*;
-use std::{collections::BTreeMap, iter, marker::PhantomData};
+use std::marker::PhantomData;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SAISBuilder;
@@ -16,6 +22,15 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, T, Im> {
         SuffixArray::new_sais(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        check_build::<T, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_sais_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -30,6 +45,15 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, u8, Im> {
         SuffixArray::new_sais_u8(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, u8, Im, Vec<X>>, BuildError> {
+        check_build::<u8, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_sais_u8_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 impl<T, B, Im> SuffixArray<B, T, Im>
@@ -38,264 +62,373 @@ where
     B: AsRef<[T]>,
     Im: IndexMode<T>,
 {
+    /// Build by SA-IS after renaming the values to the integer alphabet `0..sigma`.
     pub(crate) fn new_sais(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_sais_observed(values, mode, progress))
+    }
+}
+
+impl<B, Im> SuffixArray<B, u8, Im>
+where
+    B: AsRef<[u8]>,
+    Im: IndexMode<u8>,
+{
+    /// Build by SA-IS using the values as the alphabet directly.
+    pub(crate) fn new_sais_u8(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_sais_u8_observed(values, mode, progress))
+    }
+}
+
+impl<T, B, Im, X> SuffixArray<B, T, Im, Vec<X>>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+    X: SaIndex,
+{
+    pub(crate) fn new_sais_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
         let source = values.as_ref();
         assert_ne!(source.len(), usize::MAX);
-        let mut iter = source.iter().enumerate().rev();
-        let mut last_v = None;
-        let mut last_i = 0;
-        //get the last item
-        for (i, v) in &mut iter {
-            // if true || mode.is_index(i, v) {
-            {
-                last_v = Some(v);
-                last_i = i;
-                break;
-            }
-        }
-        let last_v = if let Some(last_v) = last_v {
-            last_v
-        } else {
-            //zero index item -> early return
-            return Self::new_zero_sized(values, mode);
-        };
-        let mut ltypes = BitVec::<usize, Lsb0>::repeat(false, last_i + 1);
-        ltypes.set(last_i, true);
-        let mut buckets = BTreeMap::new();
-        buckets.insert(last_v, (1, 0, vec![]));
-        let mut prev = last_v;
-        let mut s_flag = false;
-        let mut prev_s_i = last_i;
-        let mut full_count = 1;
-        for (i, v) in iter {
-            // if mode.is_index(i, v) {
-            {
-                full_count += 1;
-                let bucket = buckets.entry(v).or_insert_with(|| (0, 0, vec![]));
-                if prev > v || (prev == v && s_flag) {
-                    bucket.1 += 1;
-                    prev_s_i = i;
-                    s_flag = true;
-                } else {
-                    bucket.0 += 1;
-                    ltypes.set(i, true);
-                    if s_flag {
-                        s_flag = false;
-                        unsafe { buckets.get_mut(prev).unwrap_unchecked() }
-                            .2
-                            .push(prev_s_i);
-                    }
-                }
-                prev = v;
-            }
-        }
-        if s_flag {
-            unsafe { buckets.get_mut(prev).unwrap_unchecked() }
-                .2
-                .push(prev_s_i);
-        }
-        let mut indices = Vec::with_capacity(full_count);
-        let mut counter = 0;
-        for (_k, (lc, sc, lms)) in &mut buckets {
-            indices.extend(iter::repeat(usize::MAX).take(*lc + *sc - lms.len()));
-            *sc += *lc + counter;
-            *lc = counter;
-            counter = *sc;
-
-            indices.extend(lms.drain(..).rev());
-        }
-        // the last item is l type and should be inserted here.
-        ltypes.set(last_i, true);
-        unsafe {
-            let h = &mut buckets.get_mut(last_v).unwrap_unchecked().0;
-            *indices.get_unchecked_mut(*h) = last_i;
-            *h += 1;
-        }
-        for i in 0..full_count {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if ind != usize::MAX {
-                let ind_i = ind - 1;
-                // if mode.need_check() {
-                //     while !mode.is_index(ind_i, &source[ind_i]) {
-                //         ind_i -= 1;
-                //     }
-                // }
-                let b_ref = unsafe { ltypes.get_unchecked(ind_i) };
-                if b_ref == true {
-                    unsafe {
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(v).unwrap_unchecked().0;
-                        indices[*h] = ind_i;
-                        *h += 1;
-                    }
-                }
-            }
-        }
-        for i in (0..full_count).rev() {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if unsafe { ltypes.get_unchecked(ind) } == true {
-                let ind_i = ind - 1;
-                // if mode.need_check() {
-                //     while !mode.is_index(ind_i, &source[ind_i]) {
-                //         ind_i -= 1;
-                //     }
-                // }
-                let mut b_ref = unsafe { ltypes.get_unchecked_mut(ind_i) };
-                if b_ref == false {
-                    unsafe {
-                        b_ref.set(true);
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(v).unwrap_unchecked().1;
-                        *h -= 1;
-                        indices[*h] = ind_i;
-                    }
-                }
-            }
-        }
+        let (text, upper) = rename::<T, X, O>(source, progress)?;
+        let mut indices = try_vec(empty_index(), text.len())?;
+        sais(&text, &mut indices, upper, progress)?;
+        drop(text);
         Self::gen_check(source, &indices);
-        Self::check_remove_index(source, &mut indices, &mode);
-        Self {
+        Self::check_remove_index(source, &mut indices, &mode, progress)?;
+        Ok(Self {
             values,
             indices,
             mode,
             value_type: PhantomData,
-        }
+        })
     }
 }
 
-impl<B, Im> SuffixArray<B, u8, Im>
+impl<B, Im, X> SuffixArray<B, u8, Im, Vec<X>>
 where
     B: AsRef<[u8]>,
     Im: IndexMode<u8>,
+    X: SaIndex,
 {
-    pub(crate) fn new_sais_u8(values: B, mode: Im) -> Self {
+    pub(crate) fn new_sais_u8_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
         let source = values.as_ref();
         assert_ne!(source.len(), usize::MAX);
-        let mut iter = source.iter().enumerate().rev();
-        let mut last_v = None;
-        let mut last_i = 0;
-        //get the last item
-        for (i, v) in &mut iter {
-            // if mode.is_index(i, v) {
-            {
-                last_v = Some(v);
-                last_i = i;
-                break;
-            }
+        let mut indices = try_vec(empty_index(), source.len())?;
+        sais(source, &mut indices, u8::MAX as usize, progress)?;
+        Self::gen_check(source, &indices);
+        Self::check_remove_index(source, &mut indices, &mode, progress)?;
+        Ok(Self {
+            values,
+            indices,
+            mode,
+            value_type: PhantomData,
+        })
+    }
+}
+
+impl<B, Im> SuffixArray<B, u32, Im>
+where
+    B: AsRef<[u32]>,
+    Im: IndexMode<u32>,
+{
+    /// Build by SA-IS using the symbols `0..=upper` as the alphabet directly,
+    /// like [`SAISBuilderU8`] does for bytes.
+    pub(crate) fn new_sais_symbols(values: B, upper: usize, mode: Im) -> Self {
+        unobserved(|progress| {
+            let source = values.as_ref();
+            assert_ne!(source.len(), usize::MAX);
+            debug_assert!(source.iter().all(|s| *s as usize <= upper));
+            let mut indices = try_vec(empty_index(), source.len())?;
+            sais(source, &mut indices, upper, progress)?;
+            Self::gen_check(source, &indices);
+            Self::check_remove_index(source, &mut indices, &mode, progress)?;
+            Ok(Self {
+                values,
+                indices,
+                mode,
+                value_type: PhantomData,
+            })
+        })
+    }
+}
+
+/// Symbol of the integer alphabet used by [`sais`].
+pub(crate) trait Symbol: Copy + Ord {
+    fn name(self) -> usize;
+}
+
+impl Symbol for u8 {
+    #[inline]
+    fn name(self) -> usize {
+        self as usize
+    }
+}
+
+impl<X: SaIndex> Symbol for X {
+    #[inline]
+    fn name(self) -> usize {
+        self.to_usize()
+    }
+}
+
+/// Rename `values` to the integer alphabet `0..=upper` keeping the order, returns the names and `upper`.
+///
+/// The positions are sorted by their value to find the names, so the map of the distinct values isn't needed.
+pub(crate) fn rename<T: Ord, X: SaIndex, O: BuildObserver + ?Sized>(
+    values: &[T],
+    progress: &mut Progress<'_, O>,
+) -> Result<(Vec<X>, usize), BuildError> {
+    let n = values.len();
+    progress.start(Phase::Rename, comparisons(n) + n)?;
+    let mut order = try_with_capacity::<X>(n)?;
+    order.extend((0..n).map(to_index::<X>));
+    let mut done = 0;
+    progress.sort_by(&mut order, &mut done, |a, b| {
+        (values[a.to_usize()].cmp(&values[b.to_usize()]), 1)
+    })?;
+    let mut text = try_vec(to_index::<X>(0), n)?;
+    let mut name = 0;
+    for (i, p) in order.iter().enumerate() {
+        progress.step(done + i)?;
+        if i > 0 && values[order[i - 1].to_usize()] != values[p.to_usize()] {
+            name += 1;
         }
-        let last_v = if let Some(last_v) = last_v {
-            last_v
+        text[p.to_usize()] = to_index(name);
+    }
+    progress.finish()?;
+    Ok((text, name))
+}
+
+/// Rename the bytes to the integer alphabet `0..=upper` like [`rename`], by the table of the bytes present.
+pub(crate) fn rename_bytes<X: SaIndex>(values: &[u8]) -> Result<(Vec<X>, usize), BuildError> {
+    let mut names = [0; 256];
+    for &v in values {
+        names[v as usize] = 1;
+    }
+    let mut upper = 0;
+    for name in names.iter_mut() {
+        let present = *name;
+        *name = upper;
+        upper += present;
+    }
+    let mut text = try_with_capacity(values.len())?;
+    text.extend(values.iter().map(|&v| to_index::<X>(names[v as usize])));
+    Ok((text, upper.saturating_sub(1)))
+}
+
+/// Count the symbols of `s` into `bucket` and set it to the bucket heads, or the tails if `tail`.
+fn bucket_bounds<S: Symbol, X: SaIndex>(s: &[S], bucket: &mut [X], tail: bool) {
+    bucket.fill(to_index(0));
+    for v in s {
+        let c = &mut bucket[v.name()];
+        *c = to_index(c.to_usize() + 1);
+    }
+    let mut sum = 0;
+    for c in bucket.iter_mut() {
+        let count = c.to_usize();
+        sum += count;
+        *c = to_index(if tail { sum } else { sum - count });
+    }
+}
+
+/// Classify the suffixes of `s` into the bits, true for S type, the last one is L type.
+fn classify<S: Symbol, O: BuildObserver + ?Sized>(
+    s: &[S],
+    progress: &mut Progress<'_, O>,
+) -> Result<BitVec<usize, Lsb0>, BuildError> {
+    let n = s.len();
+    progress.start(Phase::Classification, n)?;
+    let mut stypes = try_bits(n)?;
+    for i in (0..n - 1).rev() {
+        progress.step(n - i)?;
+        let stype = if s[i] == s[i + 1] {
+            stypes[i + 1]
         } else {
-            //zero index item -> early return
-            return Self::new_zero_sized(values, mode);
+            s[i] < s[i + 1]
         };
-        let mut ltypes = BitVec::<usize, Lsb0>::repeat(false, last_i + 1);
-        ltypes.set(last_i, true);
-        let mut buckets = [(); 256].map(|_| (0, 0, vec![]));
-        buckets[*last_v as usize] = (1, 0, vec![]);
-        let mut prev = last_v;
-        let mut s_flag = false;
-        let mut prev_s_i = last_i;
-        let mut full_count = 1;
-        for (i, v) in iter {
-            // if mode.is_index(i, v) {
-            {
-                full_count += 1;
-                let bucket = unsafe { buckets.get_unchecked_mut(*v as usize) };
-                if prev > v || (prev == v && s_flag) {
-                    bucket.1 += 1;
-                    prev_s_i = i;
-                    s_flag = true;
-                } else {
-                    bucket.0 += 1;
-                    ltypes.set(i, true);
-                    if s_flag {
-                        s_flag = false;
-                        unsafe { buckets.get_mut(*prev as usize).unwrap_unchecked() }
-                            .2
-                            .push(prev_s_i);
-                    }
-                }
-                prev = v;
-            }
+        stypes.set(i, stype);
+    }
+    progress.finish()?;
+    Ok(stypes)
+}
+
+/// Induce the L types and then the S types from the LMS suffixes at the bucket tails of `sa`.
+fn induce<S: Symbol, X: SaIndex, O: BuildObserver + ?Sized>(
+    s: &[S],
+    sa: &mut [X],
+    stypes: &BitSlice<usize, Lsb0>,
+    bucket: &mut [X],
+    progress: &mut Progress<'_, O>,
+) -> Result<(), BuildError> {
+    let n = s.len();
+    // L-induction, the last suffix is the first L type of its bucket.
+    progress.start(Phase::LInduction, n)?;
+    bucket_bounds(s, bucket, false);
+    let mut put = |sa: &mut [X], p: usize| {
+        let h = &mut bucket[s[p].name()];
+        sa[h.to_usize()] = to_index(p);
+        *h = to_index(h.to_usize() + 1);
+    };
+    put(sa, n - 1);
+    for i in 0..n {
+        progress.step(i)?;
+        let p = sa[i].to_usize();
+        if p != X::MAX && p >= 1 && !stypes[p - 1] {
+            put(sa, p - 1);
+        }
+    }
+    progress.finish()?;
+    // S-induction
+    progress.start(Phase::SInduction, n)?;
+    bucket_bounds(s, bucket, true);
+    for i in (0..n).rev() {
+        progress.step(n - i)?;
+        let p = sa[i].to_usize();
+        if p != X::MAX && p >= 1 && stypes[p - 1] {
+            let t = &mut bucket[s[p - 1].name()];
+            *t = to_index(t.to_usize() - 1);
+            sa[t.to_usize()] = to_index(p - 1);
         }
-        let mut indices = Vec::with_capacity(full_count);
-        let mut counter = 0;
-        for (lc, sc, lms) in &mut buckets {
-            indices.extend(iter::repeat(usize::MAX).take(*lc + *sc - lms.len()));
-            *sc += *lc + counter;
-            *lc = counter;
-            counter = *sc;
+    }
+    progress.finish()?;
+    Ok(())
+}
 
-            indices.extend(lms.drain(..).rev());
+/// Recursive SA-IS (Nong, Zhang and Chan) writing the suffix array of `s` into `sa`.
+///
+/// `upper` is the max name of the symbols in `s`.
+/// The end of `s` is treated as a virtual sentinel smaller than every symbol.
+/// The reduced string and the suffix array of the next level are kept in `sa`,
+/// so a level allocates only its type bits and buckets, which are dropped while recursing.
+/// The phases are reported to `progress` on each recursion level.
+/// `s` must be shorter than [`SaIndex::MAX`] of `X` and as long as `sa`.
+pub(crate) fn sais<S: Symbol, X: SaIndex, O: BuildObserver + ?Sized>(
+    s: &[S],
+    sa: &mut [X],
+    upper: usize,
+    progress: &mut Progress<'_, O>,
+) -> Result<(), BuildError> {
+    let n = s.len();
+    debug_assert_eq!(n, sa.len());
+    match n {
+        0 => return Ok(()),
+        1 => {
+            sa[0] = to_index(0);
+            return Ok(());
         }
-        // the last item is l type and should be inserted here.
-        ltypes.set(last_i, true);
-        unsafe {
-            let h = &mut buckets.get_mut(*last_v as usize).unwrap_unchecked().0;
-            *indices.get_unchecked_mut(*h) = last_i;
-            *h += 1;
+        _ => {}
+    }
+    let empty = empty_index::<X>();
+    let stypes = classify(s, progress)?;
+    let is_lms = |p: usize| p > 0 && stypes[p] && !stypes[p - 1];
+    let mut bucket = try_vec(to_index::<X>(0), upper + 1)?;
+
+    // sort the LMS substrings by inducing from the LMS suffixes in the text order.
+    progress.start(Phase::BucketFill, n)?;
+    sa.fill(empty);
+    bucket_bounds(s, &mut bucket, true);
+    let mut m = 0;
+    for i in (1..n).rev() {
+        progress.step(n - i)?;
+        if is_lms(i) {
+            let t = &mut bucket[s[i].name()];
+            *t = to_index(t.to_usize() - 1);
+            sa[t.to_usize()] = to_index(i);
+            m += 1;
         }
-        for i in 0..full_count {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if ind != usize::MAX {
-                let ind_i = ind - 1;
-                // if mode.need_check() {
-                //     while !mode.is_index(ind_i, &source[ind_i]) {
-                //         ind_i -= 1;
-                //     }
-                // }
-                let b_ref = unsafe { ltypes.get_unchecked(ind_i) };
-                if b_ref == true {
-                    unsafe {
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(*v as usize).unwrap_unchecked().0;
-                        indices[*h] = ind_i;
-                        *h += 1;
-                    }
-                }
-            }
+    }
+    progress.finish()?;
+    induce(s, sa, &stypes, &mut bucket, progress)?;
+    if m == 0 {
+        return Ok(());
+    }
+
+    // move the sorted LMS substrings to the head and name them at `m + p / 2`,
+    // LMS positions are at least 2 apart.
+    let mut k = 0;
+    for i in 0..n {
+        let p = sa[i].to_usize();
+        if p != X::MAX && is_lms(p) {
+            sa[k] = to_index(p);
+            k += 1;
         }
-        for i in (0..full_count).rev() {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if unsafe { ltypes.get_unchecked(ind) } == true {
-                let ind_i = ind - 1;
-                // if mode.need_check() {
-                //     while !mode.is_index(ind_i, &source[ind_i]) {
-                //         ind_i -= 1;
-                //     }
-                // }
-                let mut b_ref = unsafe { ltypes.get_unchecked_mut(ind_i) };
-                if b_ref == false {
-                    unsafe {
-                        b_ref.set(true);
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(*v as usize).unwrap_unchecked().1;
-                        *h -= 1;
-                        indices[*h] = ind_i;
-                    }
-                }
+    }
+    sa[m..].fill(empty);
+    let lms_end = |p: usize| (p + 1..n).find(|&q| is_lms(q)).unwrap_or(n);
+    let mut name = 0;
+    let mut prev = None;
+    progress.start(Phase::Rename, m)?;
+    for i in 0..m {
+        progress.step(i)?;
+        let r = sa[i].to_usize();
+        let end_r = lms_end(r);
+        if let Some((l, end_l)) = prev {
+            let same =
+                end_l - l == end_r - r && end_l != n && end_r != n && s[l..=end_l] == s[r..=end_r];
+            if !same {
+                name += 1;
             }
         }
-        Self::gen_check(source, &indices);
-        Self::check_remove_index(source, &mut indices, &mode);
-        Self {
-            values,
-            indices,
-            mode,
-            value_type: PhantomData,
+        sa[m + r / 2] = to_index(name);
+        prev = Some((r, end_r));
+    }
+    progress.finish()?;
+    // pack the names to the tail as the reduced string in the text order.
+    let mut j = n;
+    for i in (m..n).rev() {
+        if sa[i] != empty {
+            j -= 1;
+            sa[j] = sa[i];
+        }
+    }
+    drop(stypes);
+    drop(bucket);
+
+    let (head, reduced) = sa.split_at_mut(n - m);
+    let reduced_sa = &mut head[..m];
+    if name + 1 == m {
+        for (i, c) in reduced.iter().enumerate() {
+            reduced_sa[c.to_usize()] = to_index(i);
         }
+    } else {
+        sais::<X, X, O>(reduced, reduced_sa, name, progress)?;
+    }
+
+    // map the ranks of the reduced string back to the LMS positions.
+    let stypes = classify(s, progress)?;
+    let is_lms = |p: usize| p > 0 && stypes[p] && !stypes[p - 1];
+    let mut j = n - m;
+    for p in 1..n {
+        if is_lms(p) {
+            sa[j] = to_index(p);
+            j += 1;
+        }
+    }
+    for i in 0..m {
+        sa[i] = sa[n - m + sa[i].to_usize()];
+    }
+    sa[m..].fill(empty);
+    let mut bucket = try_vec(to_index::<X>(0), upper + 1)?;
+
+    // put the sorted LMS suffixes at the bucket tails keeping the order, then induce the others.
+    progress.start(Phase::BucketFill, m)?;
+    bucket_bounds(s, &mut bucket, true);
+    for i in (0..m).rev() {
+        progress.step(m - i)?;
+        let p = sa[i].to_usize();
+        sa[i] = empty;
+        let t = &mut bucket[s[p].name()];
+        *t = to_index(t.to_usize() - 1);
+        sa[t.to_usize()] = to_index(p);
     }
+    progress.finish()?;
+    induce(s, sa, &stypes, &mut bucket, progress)
 }
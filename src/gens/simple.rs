@@ -1,6 +1,11 @@
-use super::{builders::Builder, IndexMode};
-use crate::SuffixArray;
-use std::{collections::BTreeMap, marker::PhantomData};
+use super::{
+    builders::{BuildError, Builder},
+    error::{check_build, try_with_capacity},
+    observer::{comparisons, unobserved, BuildObserver, Cancelled, Phase, Progress},
+    IndexMode,
+};
+use crate::{indices::SaIndex, SuffixArray};
+use std::{cmp::Ordering, marker::PhantomData};
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct NaiveBuilder;
@@ -15,6 +20,15 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, T, Im> {
         SuffixArray::new_naive(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        check_build::<T, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_naive_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -30,6 +44,15 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, T, Im> {
         SuffixArray::new_bucket(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        check_build::<T, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_bucket_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 impl<T, B, Im> SuffixArray<B, T, Im>
@@ -38,65 +61,152 @@ where
     B: AsRef<[T]>,
     Im: IndexMode<T>,
 {
-    #[inline]
-    pub(crate) fn new_zero_sized(values: B, mode: Im) -> Self {
-        Self {
-            values,
-            indices: vec![],
-            mode,
-            value_type: PhantomData,
+    pub(crate) fn new_naive(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_naive_observed(values, mode, progress))
+    }
+
+    pub(crate) fn new_bucket(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_bucket_observed(values, mode, progress))
+    }
+}
+
+/// Converts a text position which is known to fit in `X`.
+#[inline]
+pub(crate) fn to_index<X: SaIndex>(pos: usize) -> X {
+    match X::from_usize(pos) {
+        Some(x) => x,
+        None => unreachable!("position {pos} doesn't fit in the index"),
+    }
+}
+
+/// Empty slot of the indices under construction.
+///
+/// The builders only take the values shorter than [`SaIndex::MAX`], so no position is this.
+#[inline]
+pub(crate) fn empty_index<X: SaIndex>() -> X {
+    to_index(X::MAX)
+}
+
+/// Values compared at once between the checks of the cost.
+const COMPARE_BLOCK: usize = 1 << 10;
+
+/// Compare the suffixes at `a` and `b`, with the cost of the compared blocks.
+#[inline]
+fn compare_suffixes<S: Ord>(s: &[S], a: usize, b: usize) -> (Ordering, usize) {
+    let (a, b) = (&s[a..], &s[b..]);
+    let len = a.len().min(b.len());
+    let mut common = 0;
+    while common < len {
+        let end = (common + COMPARE_BLOCK).min(len);
+        if a[common..end] != b[common..end] {
+            break;
         }
+        common = end;
     }
+    (a[common..].cmp(&b[common..]), common / COMPARE_BLOCK + 1)
+}
 
-    #[inline]
-    pub(crate) fn sort_indices(values: &[T], indices: &mut [usize]) {
-        indices.sort_by_key(|x| &values[*x..]);
+/// Sort `indices` by the suffixes of `s`, counting the comparisons from `done`.
+///
+/// The suffixes are distinct, so the unstable sort needs no scratch.
+pub(crate) fn sort_suffixes<S: Ord, X: SaIndex, O: BuildObserver + ?Sized>(
+    s: &[S],
+    indices: &mut [X],
+    progress: &mut Progress<'_, O>,
+    done: &mut usize,
+) -> Result<(), Cancelled> {
+    progress.sort_by(indices, done, |a, b| {
+        compare_suffixes(s, a.to_usize(), b.to_usize())
+    })
+}
+
+impl<T, B, Im, X> SuffixArray<B, T, Im, Vec<X>>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+    X: SaIndex,
+{
+    /// Positions kept by `mode` in the text order.
+    fn indexed_positions<O: BuildObserver + ?Sized>(
+        values: &[T],
+        mode: &Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Vec<X>, BuildError> {
+        let mut indices = try_with_capacity(values.len())?;
+        for (index, value) in values.iter().enumerate() {
+            if mode.is_index(index, value) {
+                indices.push(to_index(index));
+            }
+            progress.step(index)?;
+        }
+        Ok(indices)
     }
 
-    pub(crate) fn new_naive(values: B, mode: Im) -> Self {
-        let mut indices = values
-            .as_ref()
-            .iter()
-            .enumerate()
-            .filter_map(|(index, value)| mode.is_index(index, value).then(|| index))
-            .collect::<Vec<_>>();
-        Self::sort_indices(values.as_ref(), &mut indices);
-        Self {
+    pub(crate) fn new_naive_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
+        let source = values.as_ref();
+        let len = source.len();
+        progress.start(Phase::Sort, len + comparisons(len))?;
+        let mut indices = Self::indexed_positions(source, &mode, progress)?;
+        let mut done = len;
+        sort_suffixes(source, &mut indices, progress, &mut done)?;
+        progress.finish()?;
+        Ok(Self {
             values,
             indices,
             mode,
             value_type: PhantomData,
-        }
+        })
     }
 
-    pub(crate) fn new_bucket(values: B, mode: Im) -> Self {
+    pub(crate) fn new_bucket_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
         let source = values.as_ref();
-        let mut indices = Vec::with_capacity(source.len());
-        let mut tree = BTreeMap::new();
-        for (i, v) in source.iter().enumerate() {
-            if mode.is_index(i, v) {
-                tree.entry(v).or_insert_with(Vec::new).push(i);
-            }
-        }
-        for (_, mut k_indices) in tree {
-            Self::sort_indices(source, &mut k_indices);
-            indices.append(&mut k_indices);
+        let len = source.len();
+        progress.start(Phase::BucketFill, len + comparisons(len))?;
+        let mut indices = Self::indexed_positions(source, &mode, progress)?;
+        // the buckets of the first value are the runs after sorting by it.
+        let mut done = len;
+        progress.sort_by(&mut indices, &mut done, |a, b| {
+            (source[a.to_usize()].cmp(&source[b.to_usize()]), 1)
+        })?;
+        progress.finish()?;
+        progress.start(Phase::Sort, comparisons(indices.len()))?;
+        let (mut start, mut done) = (0, 0);
+        while start < indices.len() {
+            let first = &source[indices[start].to_usize()];
+            let len = indices[start..].partition_point(|x| &source[x.to_usize()] == first);
+            sort_suffixes(
+                source,
+                &mut indices[start..start + len],
+                progress,
+                &mut done,
+            )?;
+            start += len;
         }
+        progress.finish()?;
 
-        Self {
+        Ok(Self {
             values,
             indices,
             mode,
             value_type: PhantomData,
-        }
+        })
     }
 
     #[cfg(any(feature = "gen_check", debug_assertions))]
-    pub(crate) fn gen_check(values: &[T], indices: &[usize]) {
+    pub(crate) fn gen_check(values: &[T], indices: &[X]) {
         let tmp_indices = indices
             .iter()
             .enumerate()
-            .filter(|(_, x)| **x == usize::MAX)
+            .filter(|(_, x)| x.to_usize() == X::MAX)
             .map(|(i, _)| i)
             .collect::<Vec<_>>();
         assert!(
@@ -105,16 +215,17 @@ where
         );
         indices
             .iter()
+            .map(|x| x.to_usize())
             .enumerate()
             .try_fold(None, |o, (i, x)| {
                 if let Some(o) = o {
-                    if values[o..] < values[*x..] {
-                        Ok(Some(*x))
+                    if values[o..] < values[x..] {
+                        Ok(Some(x))
                     } else {
-                        Err((i, *x))
+                        Err((i, x))
                     }
                 } else {
-                    Ok(Some(*x))
+                    Ok(Some(x))
                 }
             })
             .expect("not sorted propery");
@@ -122,20 +233,38 @@ where
 
     #[cfg(not(any(feature = "gen_check", debug_assertions)))]
     #[inline]
-    pub(crate) fn gen_check(_values: &[T], _indices: &[usize]) {}
+    pub(crate) fn gen_check(_values: &[T], _indices: &[X]) {}
 
-    pub(crate) fn check_remove_index(values: &[T], indices: &mut Vec<usize>, mode: &Im) {
+    pub(crate) fn check_remove_index<O: BuildObserver + ?Sized>(
+        values: &[T],
+        indices: &mut Vec<X>,
+        mode: &Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<(), BuildError> {
         if mode.need_check() {
-            Self::check_remove_index_inner(indices, mode, values);
+            progress.start(Phase::CheckRemoveIndex, indices.len())?;
+            Self::check_remove_index_inner(indices, mode, values, progress)?;
+            progress.finish()?;
         }
+        Ok(())
     }
 
-    fn check_remove_index_inner(indices: &mut Vec<usize>, mode: &Im, values: &[T]) {
-        for i in (0..indices.len()).rev() {
-            let index = indices[i];
-            if !mode.is_index(index, &values[index]) {
-                indices.remove(i);
+    fn check_remove_index_inner<O: BuildObserver + ?Sized>(
+        indices: &mut Vec<X>,
+        mode: &Im,
+        values: &[T],
+        progress: &mut Progress<'_, O>,
+    ) -> Result<(), BuildError> {
+        let mut done = 0;
+        let mut result = Ok(());
+        indices.retain(|&index| {
+            if result.is_ok() {
+                done += 1;
+                result = progress.step(done);
             }
-        }
+            let index = index.to_usize();
+            mode.is_index(index, &values[index])
+        });
+        Ok(result?)
     }
 }
@@ -11,7 +11,7 @@ where
 {
     let suffix = f(value, mode);
     for case in cases {
-        assert_eq!(suffix.search_naive(case.0).map(|x| x.0), case.1);
+        assert_eq!(suffix.search_naive(case.0).map(|x| x.start()), case.1);
     }
     M::test(suffix);
 }
@@ -22,7 +22,7 @@ trait ModeTester: IndexMode<u8> + Sized {
 
 impl ModeTester for () {
     fn test(array: SuffixArray<&'static str, u8, Self>) {
-        assert_eq!(array.indices.len(), array.values.bytes().len());
+        assert_eq!(array.indices.len(), array.values.len());
     }
 }
 impl ModeTester for StrIndex {
@@ -154,3 +154,497 @@ fn sais_u8_s() {
 fn sais_str_s() {
     gen_test_cases_str(SuffixArray::new_sais_u8);
 }
+
+/// Text generated by a simple LCG, over the first `sigma` letters.
+fn lcg_text(len: usize, sigma: u8, mut seed: u64) -> String {
+    (0..len)
+        .map(|_| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (b'a' + ((seed >> 33) % sigma as u64) as u8) as char
+        })
+        .collect()
+}
+
+fn adversarial_texts() -> Vec<String> {
+    let mut texts = vec![
+        String::new(),
+        "a".to_string(),
+        "ba".to_string(),
+        "ab".repeat(500),
+        "aab".repeat(300),
+        "a".repeat(1000),
+        "a".repeat(500) + &"b".repeat(500),
+        "b".repeat(500) + &"a".repeat(500),
+        "abaababaabaababaababaabaababaabaab".repeat(20),
+        "mississippi".to_string(),
+        "錆さび錆さびさびさび".repeat(30),
+    ];
+    for (i, sigma) in [1, 2, 3, 4, 26].into_iter().enumerate() {
+        texts.push(lcg_text(2000, sigma, i as u64));
+    }
+    texts
+}
+
+fn check_same_as_naive<F>(mut f: F)
+where
+    F: FnMut(&str) -> SuffixArray<&str, u8, ()>,
+{
+    for text in adversarial_texts() {
+        let expected = SuffixArray::new_naive(text.as_str(), ());
+        assert_eq!(f(&text).indices(), expected.indices(), "{text}");
+    }
+}
+
+#[test]
+fn sais_adversarial_g() {
+    check_same_as_naive(|text| SuffixArray::new_sais(text, ()));
+}
+
+#[test]
+fn sais_adversarial_s() {
+    check_same_as_naive(|text| SuffixArray::new_sais_u8(text, ()));
+}
+
+#[test]
+fn sais_reduced_alphabet() {
+    let text = lcg_text(3000, 3, 42)
+        .bytes()
+        .map(|v| v as usize * 1000)
+        .collect::<Vec<_>>();
+    let sa = SuffixArray::new_sais(text.as_slice(), ());
+    let expected = SuffixArray::new_naive(text.as_slice(), ());
+    assert_eq!(sa.indices(), expected.indices());
+}
+
+#[test]
+fn new_by_width() {
+    use crate::{gens::builders::SAISBuilderU8, indices::U40};
+    let text = "abcde錆さびacad";
+    let expected = SuffixArray::new_naive(text, StrIndex);
+    let sa = SuffixArray::<_, u8, _, Vec<u32>>::new_by_width::<SAISBuilderU8>(text, StrIndex);
+    assert!(sa
+        .indices()
+        .iter()
+        .map(|i| *i as usize)
+        .eq(expected.indices().iter().copied()));
+    let sa = SuffixArray::<_, u8, _, Vec<U40>>::new_by_width::<SAISBuilderU8>(text, StrIndex);
+    assert_eq!(sa.search_naive("さび").map(|x| x.start()), Ok(9));
+}
+
+#[test]
+fn builders_width() {
+    use crate::{gens::builders::*, indices::U40};
+    fn check<B: Builder<&'static str, u8, StrIndex>>(text: &'static str) {
+        let expected = SuffixArray::new_naive(text, StrIndex);
+        let narrow = SuffixArray::<_, u8, _, Vec<u32>>::new_by_width::<B>(text, StrIndex);
+        assert!(narrow
+            .indices()
+            .iter()
+            .map(|i| *i as usize)
+            .eq(expected.indices().iter().copied()));
+        let narrow = SuffixArray::<_, u8, _, Vec<U40>>::new_by_width::<B>(text, StrIndex);
+        assert!(narrow
+            .indices()
+            .iter()
+            .map(|i| i.get() as usize)
+            .eq(expected.indices().iter().copied()));
+    }
+    for text in ["abcde錆さびacad", "mississippi", "aaaaaaaaab", ""] {
+        check::<NaiveBuilder>(text);
+        check::<BucketBuilder>(text);
+        check::<TwoStageBuilder>(text);
+        check::<TwoStageBuilderU8>(text);
+        check::<SAISBuilder>(text);
+        check::<SAISBuilderU8>(text);
+    }
+}
+
+#[test]
+fn merge() {
+    for text in adversarial_texts() {
+        for split in [0, 1, text.len() / 3, text.len() / 2, text.len()] {
+            if !text.is_char_boundary(split) {
+                continue;
+            }
+            let (left, right) = text.split_at(split);
+            let left = SuffixArray::new_sais(left, StrIndex);
+            let right = SuffixArray::new_sais(right, StrIndex);
+            let merged = SuffixArray::merge(text.as_str(), &left, &right);
+            let expected = SuffixArray::new_naive(text.as_str(), StrIndex);
+            assert_eq!(merged.indices(), expected.indices(), "{text} {split}");
+        }
+    }
+}
+
+#[test]
+fn merge_repetitive() {
+    let text = "abcab".repeat(40000);
+    let expected = SuffixArray::new_sais(text.as_str(), ());
+    for split in [3, 99999, 100000, text.len() - 7] {
+        let (left, right) = text.split_at(split);
+        let left = SuffixArray::new_sais(left, ());
+        let right = SuffixArray::new_sais(right, ());
+        let merged = SuffixArray::merge(text.as_str(), &left, &right);
+        assert_eq!(merged.indices(), expected.indices(), "{split}");
+    }
+    let values = lcg_text(3000, 2, 3)
+        .bytes()
+        .map(|b| b as u32 * 1000)
+        .chain((0..3000).map(|v| v % 3))
+        .collect::<Vec<_>>();
+    let (left, right) = values.split_at(2000);
+    let left = SuffixArray::new_sais(left, ());
+    let right = SuffixArray::new_sais(right, ());
+    let merged = SuffixArray::merge(values.as_slice(), &left, &right);
+    let expected = SuffixArray::new_naive(values.as_slice(), ());
+    assert_eq!(merged.indices(), expected.indices());
+}
+
+#[test]
+fn merge_short_texts() {
+    // every split of short texts, where many suffixes of the left are prefixes of others.
+    for len in 2..14 {
+        for seed in 0..200 {
+            let text = lcg_text(len, 2 + (seed % 2) as u8, seed);
+            let expected = SuffixArray::new_naive(text.as_str(), ());
+            for split in 1..len {
+                let (left, right) = text.split_at(split);
+                let left = SuffixArray::new_sais(left, ());
+                let right = SuffixArray::new_sais(right, ());
+                let merged = SuffixArray::merge(text.as_str(), &left, &right);
+                assert_eq!(merged.indices(), expected.indices(), "{text} {split}");
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel() {
+    use crate::gens::builders::ParallelBuilder;
+    gen_test_cases_u8(SuffixArray::new_by::<ParallelBuilder>);
+    gen_test_cases_str(SuffixArray::new_by::<ParallelBuilder>);
+    check_same_as_naive(|text| SuffixArray::new_by::<ParallelBuilder>(text, ()));
+    let text = lcg_text(40000, 2, 7);
+    let sa = SuffixArray::new_by::<ParallelBuilder>(text.as_str(), ());
+    let expected = SuffixArray::new_naive(text.as_str(), ());
+    assert_eq!(sa.indices(), expected.indices());
+    // repetitive values recurse on every level.
+    let text = "abcab".repeat(20000);
+    let sa = SuffixArray::<_, u8, StrIndex, Vec<u32>>::new_by_width::<ParallelBuilder>(
+        text.as_str(),
+        StrIndex,
+    );
+    let expected = SuffixArray::new_sais_u8(text.as_str(), StrIndex);
+    assert!(sa
+        .indices()
+        .iter()
+        .map(|&i| i as usize)
+        .eq(expected.indices().iter().copied()));
+}
+
+#[test]
+fn try_new_by() {
+    use crate::gens::builders::{BuildError, NaiveBuilder, SAISBuilderU8};
+    let sa = SuffixArray::try_new_by::<SAISBuilderU8>("abcde錆さびacad", StrIndex).unwrap();
+    assert_eq!(
+        sa.indices(),
+        SuffixArray::new_naive("abcde錆さびacad", StrIndex).indices()
+    );
+    let sa = SuffixArray::try_new_by::<NaiveBuilder>(&[][..], StrIndex).unwrap();
+    assert!(sa.indices().is_empty());
+    let continuation = [0x80u8, 0x81, 0xbf];
+    assert_eq!(
+        SuffixArray::try_new_by::<SAISBuilderU8>(&continuation[..], StrIndex).unwrap_err(),
+        BuildError::NoIndexablePosition
+    );
+    let sa =
+        SuffixArray::<_, u8, _, Vec<u32>>::try_new_by_width::<SAISBuilderU8>("abc", ()).unwrap();
+    assert_eq!(sa.indices(), [0, 1, 2]);
+}
+
+#[test]
+fn try_new_with_budget() {
+    use crate::gens::builders::{
+        BucketBuilder, BuildError, PeakMemory, SAISBuilder, TwoStageBuilderU8,
+    };
+    let text = "abcde錆さびacad";
+    let expected = SuffixArray::new_naive(text, StrIndex);
+    for budget in [
+        usize::MAX,
+        SAISBuilder::peak_memory(text.len()),
+        TwoStageBuilderU8::peak_memory(text.len()),
+        BucketBuilder::peak_memory(text.len()),
+    ] {
+        let sa = SuffixArray::try_new_with_budget(text, StrIndex, budget).unwrap();
+        assert_eq!(sa.indices(), expected.indices());
+    }
+    assert_eq!(
+        SuffixArray::try_new_with_budget(text, StrIndex, 10).unwrap_err(),
+        BuildError::MemoryBudget {
+            required: BucketBuilder::peak_memory(text.len()),
+            budget: 10
+        }
+    );
+    assert_eq!(
+        SuffixArray::<&[u8], u8, ()>::try_new_with_budget(&[], (), 0).map(|sa| sa.indices().len()),
+        Ok(0)
+    );
+}
+
+#[test]
+fn try_new_observed() {
+    use crate::gens::builders::*;
+    use std::ops::ControlFlow;
+
+    fn observe<B: Builder<&'static str, u8, StrIndex>>(text: &'static str) -> Vec<Phase> {
+        let mut phases = vec![];
+        let sa = SuffixArray::try_new_observed::<B, _>(text, StrIndex, &mut |phase, fraction| {
+            assert!((0.0..=1.0).contains(&fraction));
+            if phases.last() != Some(&phase) {
+                phases.push(phase);
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(
+            sa.indices(),
+            SuffixArray::new_naive(text, StrIndex).indices()
+        );
+        phases
+    }
+
+    let text = "abcde錆さびacad";
+    assert_eq!(observe::<NaiveBuilder>(text), [Phase::Sort]);
+    assert_eq!(
+        observe::<BucketBuilder>(text),
+        [Phase::BucketFill, Phase::Sort]
+    );
+    for phases in [
+        observe::<TwoStageBuilder>(text),
+        observe::<TwoStageBuilderU8>(text),
+        observe::<SAISBuilder>(text),
+        observe::<SAISBuilderU8>(text),
+    ] {
+        assert!(phases.contains(&Phase::LInduction));
+        assert_eq!(phases.last(), Some(&Phase::CheckRemoveIndex));
+    }
+    assert!(observe::<SAISBuilderU8>(text).contains(&Phase::SInduction));
+    assert_eq!(observe::<SAISBuilder>(text)[0], Phase::Rename);
+
+    let mut calls = 0;
+    assert_eq!(
+        SuffixArray::try_new_observed::<SAISBuilderU8, _>(text, (), &mut |_, _| {
+            calls += 1;
+            if calls > 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap_err(),
+        BuildError::Cancelled
+    );
+    assert_eq!(calls, 3);
+
+    // the sort of long equal runs is cancelled in it.
+    let text = "ab".repeat(1 << 15);
+    for cancelled in [
+        SuffixArray::try_new_observed::<NaiveBuilder, _>(
+            text.as_str(),
+            (),
+            &mut |phase, fraction| {
+                if phase == Phase::Sort && fraction > 0.0 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        ),
+        SuffixArray::try_new_observed::<TwoStageBuilderU8, _>(
+            text.as_str(),
+            (),
+            &mut |phase, fraction| {
+                if phase == Phase::Sort && fraction > 0.0 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        ),
+    ] {
+        assert_eq!(cancelled.unwrap_err(), BuildError::Cancelled);
+    }
+}
+
+#[test]
+fn sort_patterns() {
+    use crate::gens::builders::*;
+    let len = 5000u32;
+    let increasing = (0..len).collect::<Vec<_>>();
+    let decreasing = (0..len).rev().collect::<Vec<_>>();
+    let equal = vec![7; len as usize];
+    let organ = (0..len / 2).chain((0..len / 2).rev()).collect::<Vec<_>>();
+    for values in [&increasing, &decreasing, &equal, &organ] {
+        let expected = SuffixArray::new_sais(values.as_slice(), ());
+        for sa in [
+            SuffixArray::new_by::<NaiveBuilder>(values.as_slice(), ()),
+            SuffixArray::new_by::<BucketBuilder>(values.as_slice(), ()),
+        ] {
+            assert_eq!(sa.indices(), expected.indices());
+        }
+    }
+    assert!(
+        SuffixArray::new_by::<NaiveBuilder>(increasing.as_slice(), ())
+            .indices()
+            .iter()
+            .copied()
+            .eq(0..len as usize)
+    );
+    assert!(SuffixArray::new_by::<NaiveBuilder>(equal.as_slice(), ())
+        .indices()
+        .iter()
+        .copied()
+        .eq((0..len as usize).rev()));
+}
+
+#[test]
+fn cancel_in_sort() {
+    use crate::gens::builders::*;
+    use std::ops::ControlFlow;
+
+    fn cancel_at<B: Builder<&'static str, u8, ()>>(text: &'static str) {
+        let mut finished = false;
+        for at in 0.. {
+            let mut calls = 0;
+            let result = SuffixArray::try_new_observed::<B, _>(text, (), &mut |_, _| {
+                calls += 1;
+                if calls > at {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+            match result {
+                Ok(sa) => {
+                    assert_eq!(sa.indices(), SuffixArray::new_sais(text, ()).indices());
+                    finished = true;
+                    break;
+                }
+                Err(e) => assert_eq!(e, BuildError::Cancelled, "{at}"),
+            }
+        }
+        assert!(finished);
+    }
+
+    let periodic: &'static str = "ab".repeat(3000).leak();
+    let random: &'static str = lcg_text(20000, 3, 5).leak();
+    for text in [periodic, random] {
+        cancel_at::<NaiveBuilder>(text);
+        cancel_at::<BucketBuilder>(text);
+        cancel_at::<TwoStageBuilder>(text);
+        cancel_at::<SAISBuilder>(text);
+    }
+}
+
+#[test]
+fn new_with() {
+    use crate::gens::builders::*;
+    let text = "abcde錆さびacad";
+    let expected = SuffixArray::new_naive(text, StrIndex);
+    assert_eq!(
+        SuffixArray::new_with(text, StrIndex, &SAISBuilderU8).indices(),
+        expected.indices()
+    );
+    for &algorithm in Algorithm::ALL {
+        assert_eq!(algorithm.to_string().parse(), Ok(algorithm));
+        let options = BuildOptions::new(algorithm).verify(true);
+        let sa = SuffixArray::try_new_with(text, StrIndex, &options).unwrap();
+        assert_eq!(sa.indices(), expected.indices(), "{algorithm}");
+        assert_eq!(
+            SuffixArray::new_with(text, StrIndex, &algorithm).indices(),
+            expected.indices()
+        );
+        let sa = SuffixArray::<_, u8, _, Vec<u32>>::try_new_with_width(text, StrIndex, &options)
+            .unwrap();
+        assert!(sa
+            .indices()
+            .iter()
+            .map(|i| *i as usize)
+            .eq(expected.indices().iter().copied()));
+    }
+    assert_eq!("Two_Stage".parse(), Ok(Algorithm::TwoStage));
+    assert!("quick".parse::<Algorithm>().is_err());
+    assert_eq!(Algorithm::SAIS.for_values::<u8>(), Algorithm::SAISU8);
+    assert_eq!(Algorithm::SAIS.for_values::<u32>(), Algorithm::SAIS);
+    let options = BuildOptions::new(Algorithm::SAIS).memory_budget(10);
+    assert_eq!(
+        SuffixArray::try_new_with(text, StrIndex, &options).unwrap_err(),
+        BuildError::MemoryBudget {
+            required: BucketBuilder::peak_memory(text.len()),
+            budget: 10
+        }
+    );
+    // falls back to sorting in place.
+    let options =
+        BuildOptions::new(Algorithm::SAIS).memory_budget(BucketBuilder::peak_memory(text.len()));
+    assert_eq!(
+        SuffixArray::try_new_with(text, StrIndex, &options)
+            .unwrap()
+            .indices(),
+        expected.indices()
+    );
+    // bytes fall back to the leaner byte builders first.
+    let options =
+        BuildOptions::new(Algorithm::SAIS).memory_budget(TwoStageBuilderU8::peak_memory(1000));
+    assert_eq!(options.select::<u8>(1000, 8), Ok(Algorithm::TwoStageU8));
+    assert_eq!(options.select::<u32>(1000, 8), Ok(Algorithm::Bucket));
+    let options = BuildOptions::default().index_width(IndexWidth::U32);
+    assert_eq!(
+        SuffixArray::try_new_with(text, StrIndex, &options).unwrap_err(),
+        BuildError::IndexWidth {
+            width: 4,
+            entries: 8
+        }
+    );
+    assert!(
+        SuffixArray::<_, u8, _, Vec<u32>>::try_new_with_width(text, StrIndex, &options).is_ok()
+    );
+    // the values need not be hashable.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Value(u8);
+    let values = text.bytes().map(Value).collect::<Vec<_>>();
+    let sa = SuffixArray::try_new_with(&values[..], (), &BuildOptions::new(Algorithm::TwoStage));
+    assert_eq!(
+        sa.unwrap().indices(),
+        SuffixArray::new_naive(text.as_bytes(), ()).indices()
+    );
+    let words = [3u32, 1, 4, 1, 5, 9, 2, 6];
+    assert_eq!(
+        SuffixArray::try_new_with(&words[..], (), &BuildOptions::new(Algorithm::SAISU8))
+            .unwrap_err(),
+        BuildError::UnsupportedValues
+    );
+    let sa = AnySuffixArray::try_new_with(text, StrIndex, &BuildOptions::default()).unwrap();
+    assert_eq!(sa.width(), IndexWidth::U32);
+    assert_eq!(sa.len(), expected.indices().len());
+    assert!((0..sa.len())
+        .map(|rank| sa.get(rank).unwrap())
+        .eq(expected.indices().iter().copied()));
+    let options = BuildOptions::default().index_width(IndexWidth::U40);
+    let sa = AnySuffixArray::try_new_with(text, StrIndex, &options).unwrap();
+    assert_eq!(sa.width(), IndexWidth::U40);
+    assert_eq!(sa.get(0), expected.indices().first().copied());
+    #[cfg(feature = "parallel")]
+    {
+        let options = BuildOptions::new(Algorithm::Parallel).threads(2);
+        assert_eq!(
+            SuffixArray::try_new_with(text, StrIndex, &options)
+                .unwrap()
+                .indices(),
+            expected.indices()
+        );
+    }
+}
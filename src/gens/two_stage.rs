@@ -1,6 +1,13 @@
-use super::{builders::Builder, IndexMode};
-use crate::SuffixArray;
-use std::{collections::BTreeMap, marker::PhantomData, ops::AddAssign};
+use super::{
+    builders::{BuildError, Builder},
+    error::{check_build, try_bits, try_vec},
+    observer::{comparisons, unobserved, BuildObserver, Phase, Progress},
+    sais::{rename, Symbol},
+    simple::{empty_index, sort_suffixes, to_index},
+    IndexMode,
+};
+use crate::{indices::SaIndex, SuffixArray};
+use std::marker::PhantomData;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TwoStageBuilder;
@@ -15,6 +22,15 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, T, Im> {
         SuffixArray::new_two_stage(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, T, Im, Vec<X>>, BuildError> {
+        check_build::<T, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_two_stage_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -29,222 +45,187 @@ where
     fn build(values: B, mode: Im) -> crate::SuffixArray<B, u8, Im> {
         SuffixArray::new_two_stage_u8(values, mode)
     }
+
+    fn try_build_observed<X: SaIndex, O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        observer: &mut O,
+    ) -> Result<crate::SuffixArray<B, u8, Im, Vec<X>>, BuildError> {
+        check_build::<u8, Im, X>(values.as_ref(), &mode)?;
+        SuffixArray::new_two_stage_u8_observed(values, mode, &mut Progress::new(observer))
+    }
 }
 
 impl<T, B, Im> SuffixArray<B, T, Im>
+where
+    T: Ord + core::hash::Hash,
+    B: AsRef<[T]>,
+    Im: IndexMode<T>,
+{
+    pub(crate) fn new_two_stage(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_two_stage_observed(values, mode, progress))
+    }
+}
+
+impl<B, Im> SuffixArray<B, u8, Im>
+where
+    B: AsRef<[u8]>,
+    Im: IndexMode<u8>,
+{
+    pub(crate) fn new_two_stage_u8(values: B, mode: Im) -> Self {
+        unobserved(|progress| Self::new_two_stage_u8_observed(values, mode, progress))
+    }
+}
+
+impl<T, B, Im, X> SuffixArray<B, T, Im, Vec<X>>
 where
     T: Ord,
     B: AsRef<[T]>,
     Im: IndexMode<T>,
+    X: SaIndex,
 {
-    pub(crate) fn new_two_stage(values: B, mode: Im) -> Self
-    where
-        T: core::hash::Hash,
-    {
-        use bitvec::prelude::*;
+    /// Build by [`two_stage`] after renaming the values to the integer alphabet `0..sigma`.
+    pub(crate) fn new_two_stage_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
         let source = values.as_ref();
         assert_ne!(source.len(), usize::MAX);
-        let mut iter = source.iter().enumerate().rev();
-        let mut last_v = None;
-        let mut x = 0;
-        //get the last item
-        for (i, v) in &mut iter {
-            // if mode.is_index(i, v) {
-            {
-                last_v = Some(v);
-                x = i;
-                break;
-            }
-        }
-        let last_v = if let Some(last_v) = last_v {
-            last_v
-        } else {
-            //zero index item -> early return
-            return Self::new_zero_sized(values, mode);
-        };
-        let mut ltypes = BitVec::<usize, Lsb0>::repeat(false, x + 1);
-        // ltypes.set(x, true); ignored because we know that it will be used bellow.
-        let mut buckets = BTreeMap::new();
-        let mut prev = last_v;
-        // the last item is l type.
-        buckets.insert(prev, (1, vec![]));
-        let mut ind_count = 0;
-        // Check whether the item is l or s type and throw it in the bucket.
-        for (i, v) in iter {
-            // if mode.is_index(i, v) {
-            {
-                ind_count += 1;
-                let mut bucket = buckets.entry(v).or_insert_with(|| (0, vec![]));
-                if prev < v {
-                    ltypes.set(i, true);
-                    bucket.0 += 1;
-                } else {
-                    bucket.1.push(i);
-                }
-                prev = v;
-            }
-        }
-        let mut indices = Vec::with_capacity(ind_count);
-        //l item counter
-        let mut l_count_all = 0;
-        for (_k, (ref mut l_count, ref mut s_indices)) in buckets.iter_mut() {
-            let old_len = indices.len();
-            // fill the l type slots at dummy slot.
-            indices.extend(core::iter::repeat(usize::MAX).take(*l_count));
-            l_count_all += *l_count;
-            *l_count = old_len;
-            // sort the s type and put in the slots.
-            Self::sort_indices(source, s_indices);
-            indices.append(s_indices);
-        }
-        // the last item is l type and should be inserted here.
-        unsafe {
-            let h = &mut buckets.get_mut(last_v).unwrap_unchecked().0;
-            *indices.get_unchecked_mut(*h) = x;
-            *h += 1;
-        }
-        l_count_all -= 1;
-        // fill the l types
-        for i in 0..indices.len() {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if ind != usize::MAX {
-                let ind_i = ind - 1;
-                // if mode.need_check() {
-                //     while !mode.is_index(ind_i, &source[ind_i]) {
-                //         ind_i -= 1;
-                //     }
-                // }
-                let b_ref = unsafe { ltypes.get_unchecked(ind_i) };
-                if b_ref == true {
-                    unsafe {
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(v).unwrap_unchecked().0;
-                        indices[*h] = ind_i;
-                        h.add_assign(1);
-                    }
-                    l_count_all -= 1;
-                    if l_count_all == 0 {
-                        break;
-                    }
-                }
-            }
-        }
-        Self::check_remove_index(source, &mut indices, &mode);
+        let (text, upper) = rename::<T, X, O>(source, progress)?;
+        let mut indices = two_stage(&text, upper, progress)?;
+        drop(text);
+        Self::check_remove_index(source, &mut indices, &mode, progress)?;
         Self::gen_check(source, &indices);
-        Self {
+        Ok(Self {
             values,
             indices,
             mode,
             value_type: PhantomData,
-        }
+        })
     }
 }
 
-impl<B, Im> SuffixArray<B, u8, Im>
+impl<B, Im, X> SuffixArray<B, u8, Im, Vec<X>>
 where
     B: AsRef<[u8]>,
     Im: IndexMode<u8>,
+    X: SaIndex,
 {
-    pub(crate) fn new_two_stage_u8(values: B, mode: Im) -> Self {
-        use bitvec::prelude::*;
+    pub(crate) fn new_two_stage_u8_observed<O: BuildObserver + ?Sized>(
+        values: B,
+        mode: Im,
+        progress: &mut Progress<'_, O>,
+    ) -> Result<Self, BuildError> {
         let source = values.as_ref();
         assert_ne!(source.len(), usize::MAX);
-        let mut iter = source.iter().enumerate().rev();
-        let mut last_v = None;
-        let mut x = 0;
-        //get the last item
-        for (i, v) in &mut iter {
-            // if mode.is_index(i, v) {
-            {
-                last_v = Some(v);
-                x = i;
-                break;
-            }
-        }
-        let last_v = if let Some(last_v) = last_v {
-            last_v
-        } else {
-            //zero index item -> early return
-            return Self::new_zero_sized(values, mode);
-        };
-        let mut ltypes = BitVec::<usize, Lsb0>::repeat(false, x + 1);
-        // ltypes.set(x, true); ignored because we know that it will be used bellow.
-        let mut buckets = [(); 256].map(|_| (0, vec![]));
-        let mut prev = last_v;
-        // the last item is l type.
-        buckets[*prev as usize].0 = 1;
-        let mut ind_count = 0;
-        // Check whether the item is l or s type and throw it in the bucket.
-        for (i, v) in iter {
-            // if mode.is_index(i, v) {
-            {
-                ind_count += 1;
-                let mut bucket = &mut buckets[*v as usize];
-                if prev < v {
-                    ltypes.set(i, true);
-                    bucket.0 += 1;
-                } else {
-                    bucket.1.push(i);
-                }
-                prev = v;
-            }
-        }
-        let mut indices = Vec::with_capacity(ind_count);
-        //l item counter
-        let mut l_count_all = 0;
-        for (_k, (ref mut l_count, ref mut s_indices)) in buckets.iter_mut().enumerate() {
-            let old_len = indices.len();
-            // fill the l type slots at dummy slot.
-            indices.extend(core::iter::repeat(usize::MAX).take(*l_count));
-            l_count_all += *l_count;
-            *l_count = old_len;
-            // sort the s type and put in the slots.
-            Self::sort_indices(source, s_indices);
-            indices.append(s_indices);
-        }
-        // the last item is l type and should be inserted here.
-        unsafe {
-            let h = &mut buckets.get_mut(*last_v as usize).unwrap_unchecked().0;
-            *indices.get_unchecked_mut(*h) = x;
-            *h += 1;
-        }
-        l_count_all -= 1;
-        // fill the l types
-        for i in 0..indices.len() {
-            let ind = indices[i];
-            if ind == 0 {
-                continue;
-            }
-            if ind != usize::MAX {
-                let ind_i = ind - 1;
-                // while !mode.is_index(ind_i, &source[ind_i]) {
-                //     ind_i -= 1;
-                // }
-                let b_ref = unsafe { ltypes.get_unchecked(ind_i) };
-                if b_ref == true {
-                    unsafe {
-                        let v = &source[ind_i];
-                        let h = &mut buckets.get_mut(*v as usize).unwrap_unchecked().0;
-                        indices[*h] = ind_i;
-                        h.add_assign(1);
-                    }
-                    l_count_all -= 1;
-                    if l_count_all == 0 {
-                        break;
-                    }
-                }
-            }
-        }
+        let mut indices = two_stage(source, u8::MAX as usize, progress)?;
         Self::gen_check(source, &indices);
-        Self::check_remove_index(source, &mut indices, &mode);
-        Self {
+        Self::check_remove_index(source, &mut indices, &mode, progress)?;
+        Ok(Self {
             values,
             indices,
             mode,
             value_type: PhantomData,
+        })
+    }
+}
+
+/// Count the symbols of `s` into `bucket` and set it to the bucket starts, or the ends if `end`.
+fn bucket_bounds<S: Symbol, X: SaIndex>(s: &[S], bucket: &mut [X], end: bool) {
+    let zero = to_index::<X>(0);
+    bucket.fill(zero);
+    for v in s {
+        let c = &mut bucket[v.name()];
+        *c = to_index(c.to_usize() + 1);
+    }
+    let mut sum = 0;
+    for c in bucket.iter_mut() {
+        let count = c.to_usize();
+        *c = to_index(if end { sum + count } else { sum });
+        sum += count;
+    }
+}
+
+/// Two stage sort (Itoh and Tanaka).
+///
+/// The suffixes followed by a smaller value are L type and the others are S type.
+/// The S types are sorted by comparing them in their buckets, then the L types are induced from them.
+/// `upper` is the max name of the symbols in `s`, and `s` must be shorter than [`SaIndex::MAX`] of `X`.
+pub(crate) fn two_stage<S: Symbol, X: SaIndex, O: BuildObserver + ?Sized>(
+    s: &[S],
+    upper: usize,
+    progress: &mut Progress<'_, O>,
+) -> Result<Vec<X>, BuildError> {
+    let n = s.len();
+    if n == 0 {
+        return Ok(vec![]);
+    }
+    let empty = empty_index::<X>();
+    // the last one is smaller than the longer suffixes of its bucket, so it is taken as L type.
+    let mut ltypes = try_bits(n)?;
+    ltypes.set(n - 1, true);
+    let mut l_count = 1;
+    progress.start(Phase::Classification, n)?;
+    for i in (0..n - 1).rev() {
+        progress.step(n - i)?;
+        if s[i] > s[i + 1] {
+            ltypes.set(i, true);
+            l_count += 1;
+        }
+    }
+    progress.finish()?;
+    // put the S types at the tails of their buckets, leaving the heads for the L types.
+    let mut indices = try_vec(empty, n)?;
+    let mut bucket = try_vec(to_index::<X>(0), upper + 1)?;
+    bucket_bounds(s, &mut bucket, true);
+    progress.start(Phase::BucketFill, n)?;
+    for i in (0..n).rev() {
+        progress.step(n - i)?;
+        if !ltypes[i] {
+            let t = &mut bucket[s[i].name()];
+            *t = to_index(t.to_usize() - 1);
+            indices[t.to_usize()] = to_index(i);
+        }
+    }
+    progress.finish()?;
+    // sort the runs of the S types sharing the first value.
+    progress.start(Phase::Sort, comparisons(n - l_count))?;
+    let (mut start, mut done) = (0, 0);
+    while start < n {
+        if indices[start] == empty {
+            start += 1;
+            continue;
+        }
+        let first = s[indices[start].to_usize()];
+        let len = indices[start..]
+            .iter()
+            .take_while(|&&p| p != empty && s[p.to_usize()] == first)
+            .count();
+        sort_suffixes(s, &mut indices[start..start + len], progress, &mut done)?;
+        start += len;
+    }
+    progress.finish()?;
+    // fill the L types from the bucket heads in the suffix order.
+    bucket_bounds(s, &mut bucket, false);
+    let h = &mut bucket[s[n - 1].name()];
+    indices[h.to_usize()] = to_index(n - 1);
+    *h = to_index(h.to_usize() + 1);
+    l_count -= 1;
+    progress.start(Phase::LInduction, n)?;
+    for i in 0..n {
+        if l_count == 0 {
+            break;
+        }
+        progress.step(i)?;
+        let p = indices[i].to_usize();
+        if p != X::MAX && p >= 1 && ltypes[p - 1] {
+            let h = &mut bucket[s[p - 1].name()];
+            indices[h.to_usize()] = to_index(p - 1);
+            *h = to_index(h.to_usize() + 1);
+            l_count -= 1;
         }
     }
+    progress.finish()?;
+    Ok(indices)
 }
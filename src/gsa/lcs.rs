@@ -0,0 +1,178 @@
+//! Longest common substring of `k` of the documents.
+//!
+//! A window of ranks slides over the suffix array of the concatenated documents, keeping the
+//! suffixes of at least `k` documents, and the minimum lcp in the window is a common prefix of them.
+
+use std::collections::VecDeque;
+
+use crate::{gens::IndexMode, indices::SaIndex};
+
+use super::{doc_position, GeneralizedSuffixArray};
+
+/// Substring common to some documents, with its occurrences.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommonSubstring {
+    len: usize,
+    positions: Vec<(usize, usize)>,
+}
+
+impl CommonSubstring {
+    /// Length of the substring.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the substring is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// All occurrences as `(doc_id, offset)`, in ascending order.
+    #[inline]
+    pub fn positions(&self) -> &[(usize, usize)] {
+        &self.positions
+    }
+}
+
+impl<T, M> GeneralizedSuffixArray<T, M>
+where
+    T: Ord + Clone,
+    M: IndexMode<T>,
+{
+    /// Find the longest substring occurring in at least `k` documents.
+    ///
+    /// The length is trimmed to end at a position kept by the [`IndexMode`] or at the end of a document.
+    /// Returns [`None`] if no non empty substring is shared by `k` documents.
+    ///
+    /// # Panics
+    /// Panics if `k` is `0`.
+    pub fn longest_common_substring(&self, k: usize) -> Option<CommonSubstring> {
+        assert_ne!(k, 0, "k should be at least 1");
+        let indices = self.sa.indices();
+        let values = self.sa.values();
+        if k > self.doc_count() {
+            return None;
+        }
+        let lcp = self.sa.lcp();
+        let mode = self.sa.mode();
+        // trim the common prefix of `len` at `start` to end at an index.
+        let trim = |start: usize, len: usize| {
+            (1..=len)
+                .rev()
+                .find(|&len| match mode.value(values[start + len]) {
+                    None => true,
+                    Some(v) => mode.is_value_index(start + len, v),
+                })
+                .unwrap_or(0)
+        };
+
+        let doc_of = |rank: usize| doc_position(self.starts(), indices[rank]).0;
+        let mut counts = vec![0; self.doc_count()];
+        let mut distinct = 0;
+        // ranks in `l + 1..=r` with increasing lcp values.
+        let mut mins = VecDeque::new();
+        let mut best: Option<(usize, usize)> = None;
+        let mut l = 0;
+        for r in 0..indices.len() {
+            let doc = doc_of(r);
+            counts[doc] += 1;
+            if counts[doc] == 1 {
+                distinct += 1;
+            }
+            if r > l {
+                while mins.back().is_some_and(|&b| lcp[b] >= lcp[r]) {
+                    mins.pop_back();
+                }
+                mins.push_back(r);
+            }
+            while l < r && counts[doc_of(l)] > 1 || distinct > k {
+                let doc = doc_of(l);
+                counts[doc] -= 1;
+                if counts[doc] == 0 {
+                    distinct -= 1;
+                }
+                l += 1;
+                while mins.front().is_some_and(|&f| f <= l) {
+                    mins.pop_front();
+                }
+            }
+            if distinct < k {
+                continue;
+            }
+            let start = indices[r].to_usize();
+            let depth = match mins.front() {
+                Some(&f) => lcp[f],
+                None => self.starts()[doc + 1] - 1 - start,
+            };
+            if depth > best.map_or(0, |(len, _)| len) {
+                let len = trim(start, depth);
+                if len > best.map_or(0, |(len, _)| len) {
+                    best = Some((len, start));
+                }
+            }
+        }
+
+        let (len, start) = best?;
+        let mut positions = self
+            .sa
+            .find_iter(&values[start..start + len])
+            .map(|p| doc_position(self.starts(), p))
+            .collect::<Vec<_>>();
+        positions.sort_unstable();
+        Some(CommonSubstring { len, positions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gens::{IndexMode, StrIndex};
+
+    use super::super::GeneralizedSuffixArray;
+
+    /// Length of the longest substring on char boundaries in at least `k` of `docs`.
+    fn naive_lcs(docs: &[&str], k: usize) -> usize {
+        let mut best = 0;
+        for doc in docs {
+            for (start, _) in doc.char_indices() {
+                for end in (start + 1..=doc.len()).filter(|&e| doc.is_char_boundary(e)) {
+                    let s = &doc[start..end];
+                    if s.len() > best && docs.iter().filter(|d| d.contains(s)).count() >= k {
+                        best = s.len();
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn check<M: IndexMode<u8> + Default>(docs: &[&str]) {
+        let gsa = GeneralizedSuffixArray::<u8, M>::new(docs);
+        for k in 1..=docs.len() + 1 {
+            let expected = naive_lcs(docs, k);
+            let Some(found) = gsa.longest_common_substring(k) else {
+                assert_eq!(expected, 0, "{docs:?} {k}");
+                continue;
+            };
+            assert_eq!(found.len(), expected, "{docs:?} {k}");
+            let (doc, offset) = found.positions()[0];
+            let s = &docs[doc][offset..offset + found.len()];
+            let mut containing = found.positions().iter().map(|p| p.0).collect::<Vec<_>>();
+            containing.dedup();
+            assert!(containing.len() >= k);
+            for &(doc, offset) in found.positions() {
+                assert!(docs[doc][offset..].starts_with(s));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lcs() {
+        check::<()>(&["xabcdey", "zzabcdzz", "abcde"]);
+        check::<()>(&["aaaa", "aa", "", "baaab"]);
+        check::<()>(&["mississippi", "missouri", "sip", "ppi"]);
+        check::<StrIndex>(&["錆さび錆", "さびさぶ", "錆さぶ"]);
+        check::<StrIndex>(&["aあい", "bあう", "cあいう"]);
+    }
+}
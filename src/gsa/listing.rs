@@ -0,0 +1,268 @@
+//! Document listing by Muthukrishnan's algorithm.
+//!
+//! For each rank, the document array keeps the document of the suffix, and `prev` keeps the
+//! previous rank of the same document. In a range `l..r`, the ranks with `prev < l` are exactly the
+//! first occurrence of each document, found by repeated range minimum queries on `prev`.
+//!
+//! The document array is a wavelet matrix, which also counts a document in a range,
+//! and the range minimum structure keeps only the minima of the blocks of `prev`.
+
+use crate::{fm::WaveletMatrix, indices::SaIndex, search::MatchRange};
+
+use super::{doc_position, GeneralizedSuffixArray};
+
+/// Marker for the first rank of a document in `prev`.
+const NONE: usize = usize::MAX;
+
+/// Document array and range minimum structure to list the documents in a [`MatchRange`].
+///
+/// Created by [`GeneralizedSuffixArray::document_listing`].
+#[derive(Debug, Clone)]
+pub struct DocumentListing {
+    docs: WaveletMatrix,
+    // previous rank of the same document, shifted by one so that `0` is none.
+    prev: Vec<usize>,
+    min: RangeMin,
+}
+
+impl<T: Ord, M> GeneralizedSuffixArray<T, M> {
+    /// Build the [`DocumentListing`] of this array.
+    pub fn document_listing(&self) -> DocumentListing {
+        let indices = self.sa.indices();
+        let doc_count = self.starts().len() - 1;
+        let mut last = vec![NONE; doc_count];
+        let mut docs = Vec::with_capacity(indices.len());
+        let mut prev = Vec::with_capacity(indices.len());
+        for (rank, p) in indices.iter().enumerate() {
+            let (doc, _) = doc_position(self.starts(), p.to_usize());
+            docs.push(doc);
+            prev.push(last[doc].wrapping_add(1));
+            last[doc] = rank;
+        }
+        DocumentListing {
+            docs: WaveletMatrix::new(docs, doc_count),
+            min: RangeMin::new(&prev),
+            prev,
+        }
+    }
+}
+
+impl DocumentListing {
+    /// Document of the suffix at `rank`.
+    #[inline]
+    pub fn doc_of_rank(&self, rank: usize) -> usize {
+        self.docs.get(rank)
+    }
+
+    /// Distinct documents having a suffix in `range`, in no particular order.
+    ///
+    /// Runs in time proportional to the number of the documents,
+    /// each a range minimum query scanning at most two blocks.
+    pub fn list(&self, range: MatchRange) -> Vec<usize> {
+        let mut docs = vec![];
+        let l = range.start();
+        let mut stack = vec![range.ranks()];
+        while let Some(r) = stack.pop() {
+            if r.is_empty() {
+                continue;
+            }
+            let p = self.min.argmin(&self.prev, r.start, r.end);
+            // `prev[p] < l + 1` means the previous rank of the document is before `l`.
+            if self.prev[p] > l {
+                continue;
+            }
+            docs.push(self.docs.get(p));
+            stack.push(r.start..p);
+            stack.push(p + 1..r.end);
+        }
+        docs
+    }
+
+    /// Distinct documents in `range` with the number of the suffixes in each.
+    pub fn frequencies(&self, range: MatchRange) -> Vec<(usize, usize)> {
+        self.list(range)
+            .into_iter()
+            .map(|doc| {
+                let count = self.docs.rank(doc, range.end()) - self.docs.rank(doc, range.start());
+                (doc, count)
+            })
+            .collect()
+    }
+}
+
+/// Range minimum over the values kept by the caller, with a sparse table over the minima of the blocks.
+///
+/// A query scans the partial blocks at both ends, so the table has `n / BLOCK * log n` entries,
+/// and the blocks are counted in `u32`.
+#[derive(Debug, Clone)]
+struct RangeMin {
+    /// offset of the minimum in each block
+    offsets: Vec<u8>,
+    // `table[k][i]` is the block of the minimum in the blocks `i..i + 2^(k + 1)`.
+    table: Vec<Vec<u32>>,
+}
+
+impl RangeMin {
+    const BLOCK: usize = 64;
+
+    fn new(values: &[usize]) -> Self {
+        assert!(
+            values.len() / Self::BLOCK <= u32::MAX as usize,
+            "too many blocks for u32"
+        );
+        let offsets = values
+            .chunks(Self::BLOCK)
+            .map(|block| argmin_in(block, 0, block.len()) as u8)
+            .collect::<Vec<_>>();
+        let min_of = |block: u32| {
+            let block = block as usize;
+            values[block * Self::BLOCK + offsets[block] as usize]
+        };
+        let blocks = offsets.len();
+        let mut table: Vec<Vec<u32>> = vec![];
+        let mut width = 1;
+        while width * 2 <= blocks {
+            let level = {
+                let below = table.last();
+                (0..=blocks - width * 2)
+                    .map(|i| {
+                        let (a, b) = match below {
+                            Some(below) => (below[i], below[i + width]),
+                            None => (i as u32, i as u32 + 1),
+                        };
+                        if min_of(b) < min_of(a) {
+                            b
+                        } else {
+                            a
+                        }
+                    })
+                    .collect()
+            };
+            table.push(level);
+            width *= 2;
+        }
+        Self { offsets, table }
+    }
+
+    /// Position of the minimum of `values` in the non empty range `l..r`,
+    /// `values` must be the ones this is built from.
+    fn argmin(&self, values: &[usize], l: usize, r: usize) -> usize {
+        let (first, last) = (l / Self::BLOCK, (r - 1) / Self::BLOCK);
+        if first == last {
+            return argmin_in(values, l, r);
+        }
+        let mut candidates = [
+            argmin_in(values, l, (first + 1) * Self::BLOCK),
+            argmin_in(values, last * Self::BLOCK, r),
+            NONE,
+        ];
+        if first + 1 < last {
+            let block = self.block_argmin(values, first + 1, last);
+            candidates[2] = block * Self::BLOCK + self.offsets[block] as usize;
+        }
+        candidates
+            .into_iter()
+            .filter(|&p| p != NONE)
+            .min_by_key(|&p| values[p])
+            .unwrap()
+    }
+
+    /// Block of the minimum in the non empty blocks `l..r`.
+    fn block_argmin(&self, values: &[usize], l: usize, r: usize) -> usize {
+        let len = r - l;
+        if len == 1 {
+            return l;
+        }
+        let k = (usize::BITS - 1 - len.leading_zeros()) as usize;
+        let level = &self.table[k - 1];
+        let (a, b) = (level[l] as usize, level[r - (1 << k)] as usize);
+        let min_of = |block: usize| values[block * Self::BLOCK + self.offsets[block] as usize];
+        if min_of(b) < min_of(a) {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+/// Position of the minimum of `values` in the non empty range `l..r` by scanning.
+#[inline]
+fn argmin_in(values: &[usize], l: usize, r: usize) -> usize {
+    (l..r).min_by_key(|&p| values[p]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{super::GeneralizedSuffixArray, RangeMin};
+
+    #[test]
+    fn test_listing() {
+        let docs = ["abcab", "", "bcabc", "ababab", "cccc", "abc", "ca"];
+        let gsa = GeneralizedSuffixArray::<u8>::new(docs);
+        let listing = gsa.document_listing();
+        for pattern in ["a", "ab", "abc", "b", "c", "cc", "ca", "x", ""] {
+            let Ok(range) = gsa.search(pattern) else {
+                assert!(gsa.find_iter(pattern).next().is_none());
+                continue;
+            };
+            let mut expected = BTreeMap::new();
+            for (doc, _) in gsa.find_iter(pattern) {
+                *expected.entry(doc).or_insert(0) += 1;
+            }
+            let mut docs = listing.list(range);
+            docs.sort_unstable();
+            assert_eq!(
+                docs,
+                expected.keys().copied().collect::<Vec<_>>(),
+                "{pattern}"
+            );
+            let frequencies = listing
+                .frequencies(range)
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            assert_eq!(frequencies, expected, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_range_min() {
+        let mut state = 3u32;
+        let values = (0..300)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as usize % 50
+            })
+            .collect::<Vec<_>>();
+        let min = RangeMin::new(&values);
+        for l in 0..values.len() {
+            for r in l + 1..=values.len() {
+                let p = min.argmin(&values, l, r);
+                assert!((l..r).contains(&p));
+                assert_eq!(values[p], *values[l..r].iter().min().unwrap(), "{l}..{r}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_listing_long() {
+        let docs = (0..40)
+            .map(|i| "abcab".repeat(i % 7) + &"ba".repeat(i % 5) + "c")
+            .collect::<Vec<_>>();
+        let gsa = GeneralizedSuffixArray::<u8>::new(&docs);
+        let listing = gsa.document_listing();
+        for pattern in ["a", "ab", "cab", "bab", "bc", "c", "abcabc"] {
+            let range = gsa.search(pattern).unwrap();
+            let mut expected = BTreeMap::new();
+            for (doc, _) in gsa.find_iter(pattern) {
+                *expected.entry(doc).or_insert(0) += 1;
+            }
+            let frequencies = listing
+                .frequencies(range)
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            assert_eq!(frequencies, expected, "{pattern}");
+        }
+    }
+}
@@ -0,0 +1,406 @@
+//! Generalized suffix array over multiple documents.
+//!
+//! The documents are concatenated into one text of `u32` symbols, each followed by a
+//! separator unique to the document, so no match crosses a document boundary.
+//! The separators are the symbols `0..doc_count` and the values are mapped to the symbols above them
+//! in their order, so the text takes 4 bytes for each value and is an integer alphabet SA-IS builds directly.
+//! The suffixes starting at separators are filtered by [`DocMode`].
+
+use std::{
+    any::{Any, TypeId},
+    mem::size_of,
+    ops::Range,
+};
+
+use bitvec::prelude::*;
+
+mod lcs;
+mod listing;
+pub use lcs::*;
+pub use listing::*;
+
+use crate::{
+    gens::{builders::Builder, IndexMode},
+    search::{MatchRange, Positions},
+    SuffixArray,
+};
+
+/// [`IndexMode`] of the concatenated symbols, removing the separators and checking values by the inner mode.
+///
+/// The inner mode is called with the offsets of the values in their documents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DocMode<T, M> {
+    mode: M,
+    doc_count: usize,
+    /// distinct values of the documents in order, the value of the symbol `doc_count + i` at `i`
+    alphabet: Vec<T>,
+    /// start of each document in the concatenated text, and the total length at last.
+    starts: Vec<usize>,
+}
+
+impl<T, M> DocMode<T, M> {
+    /// Get a reference to the inner mode.
+    #[inline]
+    pub fn inner(&self) -> &M {
+        &self.mode
+    }
+
+    /// The value of `symbol`, or [`None`] for a separator.
+    #[inline]
+    pub fn value(&self, symbol: u32) -> Option<&T> {
+        (symbol as usize)
+            .checked_sub(self.doc_count)
+            .map(|i| &self.alphabet[i])
+    }
+}
+
+impl<T: Ord, M> DocMode<T, M> {
+    /// The symbol of `value`, or the symbol of the next larger value if it is not in the documents.
+    #[inline]
+    fn symbol(&self, value: &T) -> Result<u32, u32> {
+        match self.alphabet.binary_search(value) {
+            Ok(i) => Ok((self.doc_count + i) as u32),
+            Err(i) => Err((self.doc_count + i) as u32),
+        }
+    }
+}
+
+impl<T, M: IndexMode<T>> DocMode<T, M> {
+    /// Check the value `v` at `pos` in the concatenated text by the inner mode at its offset in the document.
+    #[inline]
+    fn is_value_index(&self, pos: usize, v: &T) -> bool {
+        !self.mode.need_check() || self.mode.is_index(doc_position(&self.starts, pos).1, v)
+    }
+}
+
+impl<T, M: IndexMode<T>> IndexMode<u32> for DocMode<T, M> {
+    #[inline]
+    fn is_index(&self, pos: usize, symbol: &u32) -> bool {
+        match self.value(*symbol) {
+            None => false,
+            Some(v) => self.is_value_index(pos, v),
+        }
+    }
+}
+
+/// Suffix array of the concatenated documents.
+pub type DocSuffixArray<T, M> = SuffixArray<Vec<u32>, u32, DocMode<T, M>>;
+
+/// Suffix array over multiple documents, returning `(doc_id, offset)` hits.
+#[derive(Debug, Clone)]
+pub struct GeneralizedSuffixArray<T, M = ()> {
+    sa: DocSuffixArray<T, M>,
+}
+
+impl<T, M> GeneralizedSuffixArray<T, M> {
+    /// Start of each document in the concatenated text, and the total length at last.
+    #[inline]
+    fn starts(&self) -> &[usize] {
+        &self.sa.mode().starts
+    }
+}
+
+impl<T, M> GeneralizedSuffixArray<T, M>
+where
+    T: Ord + Clone + 'static,
+    M: IndexMode<T>,
+{
+    /// Build from `docs` by SA-IS over the symbols without renaming them.
+    ///
+    /// # Panics
+    /// Panics if the documents and their distinct values are more than the `u32` symbols.
+    pub fn new<D>(docs: impl IntoIterator<Item = D>) -> Self
+    where
+        D: AsRef<[T]>,
+        M: Default,
+    {
+        let (text, mode) = concat(docs, M::default());
+        let upper = (mode.doc_count + mode.alphabet.len()).saturating_sub(1);
+        Self {
+            sa: SuffixArray::new_sais_symbols(text, upper, mode),
+        }
+    }
+
+    /// Build from `docs` by the [`Builder`] `B`.
+    ///
+    /// `B` should work on the `u32` symbols, so the builders only for `u8` can't be used.
+    ///
+    /// # Panics
+    /// Panics if the documents and their distinct values are more than the `u32` symbols.
+    pub fn new_by<B, D>(docs: impl IntoIterator<Item = D>, mode: M) -> Self
+    where
+        B: Builder<Vec<u32>, u32, DocMode<T, M>>,
+        D: AsRef<[T]>,
+    {
+        let (text, mode) = concat(docs, mode);
+        Self {
+            sa: SuffixArray::new_by::<B>(text, mode),
+        }
+    }
+}
+
+impl<T, M> GeneralizedSuffixArray<T, M>
+where
+    T: Ord + Clone,
+    M: IndexMode<T>,
+{
+    /// Get a reference to the suffix array of the concatenated documents.
+    #[inline]
+    pub fn suffix_array(&self) -> &DocSuffixArray<T, M> {
+        &self.sa
+    }
+
+    /// Number of the documents.
+    #[inline]
+    pub fn doc_count(&self) -> usize {
+        self.starts().len() - 1
+    }
+
+    /// Range of the document `doc_id` in the concatenated text, without its separator.
+    ///
+    /// # Panics
+    /// Panics if `doc_id` is out of range.
+    #[inline]
+    pub fn doc_range(&self, doc_id: usize) -> Range<usize> {
+        self.starts()[doc_id]..self.starts()[doc_id + 1] - 1
+    }
+
+    /// Convert a position in the concatenated text to `(doc_id, offset)`.
+    #[inline]
+    pub fn doc_position(&self, pos: usize) -> (usize, usize) {
+        doc_position(self.starts(), pos)
+    }
+
+    /// Search `pattern`, returning the ranks in the [`suffix_array`](`Self::suffix_array`).
+    pub fn search(&self, pattern: impl AsRef<[T]>) -> Result<MatchRange, usize> {
+        let mode = self.sa.mode();
+        let mut symbols = Vec::with_capacity(pattern.as_ref().len());
+        for v in pattern.as_ref() {
+            match mode.symbol(v) {
+                Ok(symbol) => symbols.push(symbol),
+                Err(next) => {
+                    // the absent value sorts just before the next larger one.
+                    symbols.push(next);
+                    return Err(match self.sa.search_naive(&symbols) {
+                        Ok(range) => range.start(),
+                        Err(rank) => rank,
+                    });
+                }
+            }
+        }
+        self.sa.search_naive(symbols)
+    }
+
+    /// Iterate the `(doc_id, offset)` where `pattern` occurs, in suffix order.
+    pub fn find_iter(&self, pattern: impl AsRef<[T]>) -> DocFindIter<'_> {
+        let range = self.search(pattern).unwrap_or_default();
+        DocFindIter {
+            range,
+            positions: range.positions(&self.sa),
+            starts: self.starts(),
+        }
+    }
+}
+
+/// Concatenate `docs` into the symbols followed by their separators, returns the symbols and the mode.
+fn concat<T, M, D>(docs: impl IntoIterator<Item = D>, mode: M) -> (Vec<u32>, DocMode<T, M>)
+where
+    T: Ord + Clone + 'static,
+    D: AsRef<[T]>,
+{
+    let docs = docs.into_iter().collect::<Vec<_>>();
+    let alphabet = present::<T, D, u8>(&docs)
+        .or_else(|| present::<T, D, u16>(&docs))
+        .unwrap_or_else(|| {
+            let mut alphabet = docs
+                .iter()
+                .flat_map(|doc| doc.as_ref().iter().cloned())
+                .collect::<Vec<_>>();
+            alphabet.sort_unstable();
+            alphabet.dedup();
+            alphabet
+        });
+    assert!(
+        docs.len() + alphabet.len() <= u32::MAX as usize + 1,
+        "{} documents and {} values are more than the u32 symbols",
+        docs.len(),
+        alphabet.len()
+    );
+    let mut starts = Vec::with_capacity(docs.len() + 1);
+    starts.push(0);
+    for doc in &docs {
+        starts.push(starts[starts.len() - 1] + doc.as_ref().len() + 1);
+    }
+    let mode = DocMode {
+        mode,
+        doc_count: docs.len(),
+        alphabet,
+        starts,
+    };
+    let mut text = Vec::with_capacity(mode.starts[docs.len()]);
+    for (id, doc) in docs.iter().enumerate() {
+        text.extend(
+            doc.as_ref()
+                .iter()
+                .map(|v| mode.symbol(v).expect("every value is in the alphabet")),
+        );
+        text.push(id as u32);
+    }
+    (text, mode)
+}
+
+/// The distinct values of `docs` in order by the table of the present values if `T` is the small `S`,
+/// instead of sorting them.
+fn present<T, D, S>(docs: &[D]) -> Option<Vec<T>>
+where
+    T: 'static,
+    D: AsRef<[T]>,
+    S: Copy + Into<usize> + TryFrom<usize> + 'static,
+{
+    if TypeId::of::<T>() != TypeId::of::<S>() {
+        return None;
+    }
+    let mut present = bitvec![0; 1 << (8 * size_of::<S>())];
+    for doc in docs {
+        for v in doc.as_ref() {
+            present.set((*(v as &dyn Any).downcast_ref::<S>()?).into(), true);
+        }
+    }
+    let alphabet = present
+        .iter_ones()
+        .filter_map(|s| S::try_from(s).ok())
+        .collect::<Vec<_>>();
+    (Box::new(alphabet) as Box<dyn Any>)
+        .downcast::<Vec<T>>()
+        .ok()
+        .map(|alphabet| *alphabet)
+}
+
+fn doc_position(starts: &[usize], pos: usize) -> (usize, usize) {
+    let doc_id = starts.partition_point(|&s| s <= pos) - 1;
+    (doc_id, pos - starts[doc_id])
+}
+
+/// Iterator of the `(doc_id, offset)` hits. Created by [`GeneralizedSuffixArray::find_iter`].
+#[derive(Debug, Clone)]
+pub struct DocFindIter<'a> {
+    range: MatchRange,
+    positions: Positions<'a>,
+    starts: &'a [usize],
+}
+
+impl DocFindIter<'_> {
+    /// The [`MatchRange`] which this iterator is created from.
+    #[inline]
+    pub fn range(&self) -> MatchRange {
+        self.range
+    }
+}
+
+impl Iterator for DocFindIter<'_> {
+    type Item = (usize, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, usize)> {
+        self.positions.next().map(|p| doc_position(self.starts, p))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.positions.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for DocFindIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<(usize, usize)> {
+        self.positions
+            .next_back()
+            .map(|p| doc_position(self.starts, p))
+    }
+}
+
+impl ExactSizeIterator for DocFindIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::GeneralizedSuffixArray;
+    use crate::gens::{
+        builders::{BucketBuilder, NaiveBuilder, SAISBuilder, TwoStageBuilder},
+        IndexMode, StrIndex,
+    };
+
+    fn naive_hits(docs: &[&str], pattern: &str) -> Vec<(usize, usize)> {
+        let mut hits = vec![];
+        for (id, doc) in docs.iter().enumerate() {
+            hits.extend(
+                (0..doc.len())
+                    .filter(|&p| doc.is_char_boundary(p) && doc[p..].starts_with(pattern))
+                    .map(|p| (id, p)),
+            );
+        }
+        hits
+    }
+
+    #[test]
+    fn test_gsa() {
+        let docs = ["abcab", "", "bcabc", "錆さび", "さびab", "ab"];
+        let built = [
+            GeneralizedSuffixArray::<u8, StrIndex>::new(docs),
+            GeneralizedSuffixArray::new_by::<NaiveBuilder, _>(docs, StrIndex),
+            GeneralizedSuffixArray::new_by::<BucketBuilder, _>(docs, StrIndex),
+            GeneralizedSuffixArray::new_by::<TwoStageBuilder, _>(docs, StrIndex),
+            GeneralizedSuffixArray::new_by::<SAISBuilder, _>(docs, StrIndex),
+        ];
+        for gsa in built {
+            assert_eq!(gsa.doc_count(), docs.len());
+            assert_eq!(gsa.doc_range(2), 7..12);
+            assert_eq!(gsa.doc_position(8), (2, 1));
+            for pattern in ["ab", "abca", "bab", "cabc", "さび", "びa", "b", "x"] {
+                let mut hits = gsa.find_iter(pattern).collect::<Vec<_>>();
+                hits.sort_unstable();
+                assert_eq!(hits, naive_hits(&docs, pattern), "{pattern}");
+            }
+            assert_eq!(
+                gsa.search("").unwrap().len(),
+                docs.iter().map(|d| d.chars().count()).sum::<usize>()
+            );
+            // the values of the suffix, a separator is `None` ordered before any value.
+            let sa = gsa.suffix_array();
+            let suffix = |rank: usize| {
+                sa.values()[sa.indices()[rank]..]
+                    .iter()
+                    .map(|s| sa.mode().value(*s))
+            };
+            for pattern in ["x", "abx", "b\0", "\0", "さ\u{7f}", "~~"] {
+                let rank = gsa.search(pattern).unwrap_err();
+                let pattern = pattern.as_bytes().iter().map(Some);
+                assert!(rank == 0 || suffix(rank - 1).lt(pattern.clone()));
+                assert!(rank == sa.indices().len() || suffix(rank).gt(pattern));
+            }
+        }
+    }
+
+    /// Keeps the even offsets in each document.
+    #[derive(Debug, Default)]
+    struct Even;
+
+    impl<T> IndexMode<T> for Even {
+        fn is_index(&self, pos: usize, _value: &T) -> bool {
+            pos.is_multiple_of(2)
+        }
+    }
+
+    #[test]
+    fn test_gsa_offsets() {
+        let docs: [&[u16]; 3] = [&[1, 2, 1], &[2, 1], &[700, 1, 2, 1]];
+        let gsa = GeneralizedSuffixArray::<u16, Even>::new(docs);
+        assert_eq!(gsa.suffix_array().mode().alphabet, [1, 2, 700]);
+        let mut hits = gsa.find_iter([1]).collect::<Vec<_>>();
+        hits.sort_unstable();
+        assert_eq!(hits, [(0, 0), (0, 2)]);
+        let wide = GeneralizedSuffixArray::<u32, Even>::new([[5u32, 3, 5], [3, 5, 3]]);
+        assert_eq!(wide.suffix_array().mode().alphabet, [3, 5]);
+        assert_eq!(wide.find_iter([3]).collect::<Vec<_>>(), [(1, 2), (1, 0)]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Storage of the indices of a [`SuffixArray`](`crate::SuffixArray`).
+//!
+//! The builders produce [`Vec`] of any [`SaIndex`], see [`new_by_width`](`crate::SuffixArray::new_by_width`),
+//! but any [`IndexStorage`] can back a suffix array,
+//! e.g. [`MappedIndices`](`crate::persist::MappedIndices`) over a memory-mapped file.
+//!
+//! The entries can be narrower than `usize` to save memory, see [`SaIndex`].
+//! [`u32`] holds texts up to 4 GiB and [`U40`] up to 1 TiB.
+
+use std::{borrow::Cow, cmp::Ordering, fmt, hash::Hash, marker::PhantomData, rc::Rc, sync::Arc};
+
+use crate::SuffixArray;
+
+/// Integer type of an index entry.
+pub trait SaIndex: Copy + Ord + Hash + fmt::Debug + Send + Sync + 'static {
+    /// Bytes of an entry.
+    const WIDTH: usize;
+    /// The largest text position this type can hold.
+    const MAX: usize;
+
+    /// Convert to a text position.
+    fn to_usize(self) -> usize;
+
+    /// Convert from a text position, or `None` if it is larger than [`MAX`](`SaIndex::MAX`).
+    fn from_usize(value: usize) -> Option<Self>;
+
+    /// Returns true if every position of a text of length `len` fits in this type.
+    #[inline]
+    fn fits(len: usize) -> bool {
+        len == 0 || len - 1 <= Self::MAX
+    }
+}
+
+impl SaIndex for usize {
+    const WIDTH: usize = std::mem::size_of::<usize>();
+    const MAX: usize = usize::MAX;
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self
+    }
+
+    #[inline]
+    fn from_usize(value: usize) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl SaIndex for u32 {
+    const WIDTH: usize = 4;
+    const MAX: usize = if (u32::MAX as u64) < usize::MAX as u64 {
+        u32::MAX as usize
+    } else {
+        usize::MAX
+    };
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+
+    #[inline]
+    fn from_usize(value: usize) -> Option<Self> {
+        value.try_into().ok()
+    }
+}
+
+impl SaIndex for u64 {
+    const WIDTH: usize = 8;
+    const MAX: usize = if (usize::MAX as u64) < u64::MAX {
+        usize::MAX
+    } else {
+        u64::MAX as usize
+    };
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+
+    #[inline]
+    fn from_usize(value: usize) -> Option<Self> {
+        Some(value as u64)
+    }
+}
+
+/// 40 bit unsigned integer stored in 5 little endian bytes, aligned to 1.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct U40([u8; 5]);
+
+impl U40 {
+    /// The largest value.
+    pub const MAX: u64 = (1 << 40) - 1;
+
+    /// Convert from a [`u64`], or `None` if it is larger than [`U40::MAX`].
+    #[inline]
+    pub fn new(value: u64) -> Option<Self> {
+        if value > Self::MAX {
+            return None;
+        }
+        let [b0, b1, b2, b3, b4, ..] = value.to_le_bytes();
+        Some(Self([b0, b1, b2, b3, b4]))
+    }
+
+    /// Get the value as [`u64`].
+    #[inline]
+    pub fn get(self) -> u64 {
+        let [b0, b1, b2, b3, b4] = self.0;
+        u64::from_le_bytes([b0, b1, b2, b3, b4, 0, 0, 0])
+    }
+}
+
+impl fmt::Debug for U40 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+impl PartialOrd for U40 {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for U40 {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+impl SaIndex for U40 {
+    const WIDTH: usize = 5;
+    const MAX: usize = if (U40::MAX as u128) < usize::MAX as u128 {
+        U40::MAX as usize
+    } else {
+        usize::MAX
+    };
+
+    #[inline]
+    fn to_usize(self) -> usize {
+        self.get() as usize
+    }
+
+    #[inline]
+    fn from_usize(value: usize) -> Option<Self> {
+        Self::new(value as u64)
+    }
+}
+
+/// Storage of the indices, the text positions in suffix order.
+pub trait IndexStorage {
+    /// Integer type of the entries.
+    type Index: SaIndex;
+
+    /// Get the indices as a slice.
+    fn as_slice(&self) -> &[Self::Index];
+}
+
+impl<X: SaIndex> IndexStorage for [X] {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<X: SaIndex> IndexStorage for Vec<X> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<X: SaIndex> IndexStorage for Box<[X]> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<X: SaIndex> IndexStorage for Rc<[X]> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<X: SaIndex> IndexStorage for Arc<[X]> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<X: SaIndex> IndexStorage for Cow<'_, [X]> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        self
+    }
+}
+
+impl<S: IndexStorage + ?Sized> IndexStorage for &S {
+    type Index = S::Index;
+
+    #[inline]
+    fn as_slice(&self) -> &[S::Index] {
+        (**self).as_slice()
+    }
+}
+
+impl<T, B, M> SuffixArray<B, T, M>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+{
+    /// Convert the indices to the entry type `X`.
+    ///
+    /// Returns `self` back if the values are too long for `X`.
+    pub fn with_index_width<X: SaIndex>(self) -> Result<SuffixArray<B, T, M, Vec<X>>, Self> {
+        if !X::fits(self.values.as_ref().len()) {
+            return Err(self);
+        }
+        let indices = self
+            .indices
+            .into_iter()
+            .map(|i| X::from_usize(i).unwrap())
+            .collect();
+        Ok(SuffixArray {
+            values: self.values,
+            indices,
+            mode: self.mode,
+            value_type: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SaIndex, U40};
+    use crate::SuffixArray;
+
+    #[test]
+    fn test_u40() {
+        for v in [0, 1, 255, 256, 1 << 32, U40::MAX] {
+            assert_eq!(U40::new(v).unwrap().get(), v);
+        }
+        assert_eq!(U40::new(U40::MAX + 1), None);
+        assert!(U40::new(256) > U40::new(255));
+        assert!(U40::new(1 << 32) > U40::new(u32::MAX as u64));
+        assert!(u32::fits(u32::MAX as usize + 1));
+        assert!(!u32::fits(u32::MAX as usize + 2));
+    }
+
+    #[test]
+    fn test_with_index_width() {
+        let text = "abracadabra錆さび";
+        let sa = SuffixArray::<_, u8, ()>::new(text);
+        let expected = sa.indices().to_vec();
+        let sa = sa.with_index_width::<u32>().unwrap();
+        let narrowed = sa
+            .indices()
+            .iter()
+            .map(|i| i.to_usize())
+            .collect::<Vec<_>>();
+        assert_eq!(narrowed, expected);
+        let range = sa.search_naive("abra").unwrap();
+        let mut found = range.positions(&sa).collect::<Vec<_>>();
+        found.sort_unstable();
+        assert_eq!(found, [0, 7]);
+        let sa = SuffixArray::<_, u8, ()>::new(text)
+            .with_index_width::<U40>()
+            .unwrap();
+        assert_eq!(sa.find_iter("a").count(), 5);
+        assert_eq!(
+            sa.lcp().as_slice(),
+            SuffixArray::<_, u8, ()>::new(text).lcp().as_slice()
+        );
+    }
+}
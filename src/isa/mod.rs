@@ -0,0 +1,102 @@
+//! Inverse suffix array (ISA) of a built [`SuffixArray`].
+//!
+//! The ISA maps a text position to the rank of its suffix in [`indices`](`SuffixArray::indices`),
+//! which is the inverse of [`suffix_at`](`SuffixArray::suffix_at`).
+
+use crate::{
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+/// Marker for the positions not in the indices.
+const NOT_INDEXED: usize = usize::MAX;
+
+/// Inverse suffix array.
+///
+/// Positions filtered by the [`IndexMode`](`crate::gens::IndexMode`) have no rank.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct InverseSuffixArray {
+    ranks: Vec<usize>,
+}
+
+impl InverseSuffixArray {
+    /// Rank of the suffix at text position `pos`.
+    ///
+    /// Returns [`None`] if `pos` is out of range or filtered out of the indices.
+    #[inline]
+    pub fn rank_of(&self, pos: usize) -> Option<usize> {
+        self.ranks.get(pos).copied().filter(|&r| r != NOT_INDEXED)
+    }
+
+    /// Length of the text.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// Returns true if the text is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ranks.is_empty()
+    }
+
+    /// Iterate the ranks in text order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Option<usize>> + ExactSizeIterator + '_ {
+        self.ranks.iter().map(|&r| (r != NOT_INDEXED).then_some(r))
+    }
+}
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    /// Text position of the suffix at `rank`, or [`None`] if `rank` is out of range.
+    #[inline]
+    pub fn suffix_at(&self, rank: usize) -> Option<usize> {
+        self.indices().get(rank).map(|p| p.to_usize())
+    }
+
+    /// Build the [`InverseSuffixArray`] of this suffix array.
+    pub fn inverse(&self) -> InverseSuffixArray {
+        let mut ranks = vec![NOT_INDEXED; self.values.as_ref().len()];
+        for (rank, p) in self.indices().iter().enumerate() {
+            ranks[p.to_usize()] = rank;
+        }
+        InverseSuffixArray { ranks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{gens::StrIndex, SuffixArray};
+
+    #[test]
+    fn test_inverse() {
+        let sa = SuffixArray::<_, u8, ()>::new("mississippi");
+        let isa = sa.inverse();
+        assert_eq!(isa.len(), 11);
+        for pos in 0..11 {
+            let rank = isa.rank_of(pos).unwrap();
+            assert_eq!(sa.suffix_at(rank), Some(pos));
+        }
+        assert_eq!(isa.rank_of(11), None);
+        assert_eq!(sa.suffix_at(11), None);
+        assert_eq!(isa.rank_of(10), Some(0));
+    }
+
+    #[test]
+    fn test_inverse_filtered() {
+        let text = "錆さびa";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let isa = sa.inverse();
+        for (pos, rank) in isa.iter().enumerate() {
+            assert_eq!(rank.is_some(), text.is_char_boundary(pos), "{pos}");
+            if let Some(rank) = rank {
+                assert_eq!(sa.suffix_at(rank), Some(pos));
+            }
+        }
+        assert_eq!(isa.iter().flatten().count(), sa.indices().len());
+    }
+}
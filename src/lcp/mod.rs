@@ -0,0 +1,231 @@
+//! Longest common prefix (LCP) array of a built [`SuffixArray`].
+//!
+//! The LCP array is built from [`values`](`SuffixArray::values`) and [`indices`](`SuffixArray::indices`)
+//! with the Φ (phi) algorithm, a variant of Kasai's algorithm working on text order.
+//! It also works on arrays filtered by an [`IndexMode`].
+
+use std::ops::Deref;
+
+use crate::{
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+/// Longest common prefix array.
+///
+/// `lcp[i]` is the length of the longest common prefix of the suffixes at rank `i - 1` and `i`,
+/// and `lcp[0]` is always `0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct LcpArray {
+    lcp: Vec<usize>,
+}
+
+impl LcpArray {
+    /// Get the lcp values as a slice.
+    #[inline]
+    pub fn as_slice(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /// Convert into the inner [`Vec`].
+    #[inline]
+    pub fn into_vec(self) -> Vec<usize> {
+        self.lcp
+    }
+}
+
+impl Deref for LcpArray {
+    type Target = [usize];
+
+    #[inline]
+    fn deref(&self) -> &[usize] {
+        &self.lcp
+    }
+}
+
+impl AsRef<[usize]> for LcpArray {
+    #[inline]
+    fn as_ref(&self) -> &[usize] {
+        &self.lcp
+    }
+}
+
+impl From<LcpArray> for Vec<usize> {
+    #[inline]
+    fn from(lcp: LcpArray) -> Self {
+        lcp.lcp
+    }
+}
+
+/// Marker in the Φ array for the positions not in the indices.
+const NOT_INDEXED: usize = usize::MAX;
+/// Marker in the Φ array for the suffix at rank 0.
+const FIRST: usize = usize::MAX - 1;
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    M: IndexMode<T>,
+    I: IndexStorage,
+{
+    /// Build the [`LcpArray`] of this suffix array.
+    ///
+    /// Runs in linear time if no index is filtered by the [`IndexMode`].
+    /// Use [`lcp_u8`](`SuffixArray::lcp_u8`) for `u8` values, which compares words at once.
+    pub fn lcp(&self) -> LcpArray {
+        let values = self.values.as_ref();
+        self.lcp_by(|p, q, h| {
+            h + values[p + h..]
+                .iter()
+                .zip(&values[q + h..])
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+    }
+
+    /// Φ algorithm.
+    /// `extend(p, q, h)` returns the lcp of the suffixes at `p` and `q`, knowing the first `h` values are same.
+    fn lcp_by<F>(&self, extend: F) -> LcpArray
+    where
+        F: Fn(usize, usize, usize) -> usize,
+    {
+        let values = self.values.as_ref();
+        let indices = self.indices();
+        let Some(first) = indices.first().map(|i| i.to_usize()) else {
+            return LcpArray::default();
+        };
+        let need_check = self.mode.need_check();
+        // phi[SA[i]] = SA[i - 1], and overwritten by the permuted lcp after it is read.
+        let mut phi = vec![NOT_INDEXED; values.len()];
+        phi[first] = FIRST;
+        for w in indices.windows(2) {
+            phi[w[1].to_usize()] = w[0].to_usize();
+        }
+        let mut h = 0;
+        // previous indexed position and its phi
+        let mut prev = None;
+        for (p, slot) in phi.iter_mut().enumerate() {
+            let q = *slot;
+            if q == NOT_INDEXED {
+                continue;
+            }
+            // lcp(phi[p], p) >= lcp(phi[prev], prev) - (p - prev),
+            // if the suffix at phi[prev] + (p - prev) is indexed.
+            h = match prev {
+                Some((prev_p, prev_q)) if prev_q != FIRST && h > p - prev_p => {
+                    let d = p - prev_p;
+                    let shifted = prev_q + d;
+                    if !need_check || self.mode.is_index(shifted, &values[shifted]) {
+                        h - d
+                    } else {
+                        0
+                    }
+                }
+                _ => 0,
+            };
+            if q == FIRST {
+                h = 0;
+            } else {
+                h = extend(p, q, h);
+            }
+            *slot = h;
+            prev = Some((p, q));
+        }
+        let mut lcp = indices
+            .iter()
+            .map(|p| phi[p.to_usize()])
+            .collect::<Vec<_>>();
+        lcp[0] = 0;
+        LcpArray { lcp }
+    }
+}
+
+impl<B, M, I> SuffixArray<B, u8, M, I>
+where
+    B: AsRef<[u8]>,
+    M: IndexMode<u8>,
+    I: IndexStorage,
+{
+    /// Build the [`LcpArray`] of this suffix array, comparing 8 bytes at once.
+    pub fn lcp_u8(&self) -> LcpArray {
+        let values = self.values.as_ref();
+        self.lcp_by(|p, q, h| h + common_prefix_u8(&values[p + h..], &values[q + h..]))
+    }
+}
+
+/// Length of the common prefix of `a` and `b`.
+pub(crate) fn common_prefix_u8(a: &[u8], b: &[u8]) -> usize {
+    const WORD: usize = std::mem::size_of::<u64>();
+    let len = a.len().min(b.len());
+    let mut i = 0;
+    while i + WORD <= len {
+        let x = u64::from_le_bytes(a[i..i + WORD].try_into().unwrap());
+        let y = u64::from_le_bytes(b[i..i + WORD].try_into().unwrap());
+        let diff = x ^ y;
+        if diff != 0 {
+            return i + (diff.trailing_zeros() / 8) as usize;
+        }
+        i += WORD;
+    }
+    i + a[i..len]
+        .iter()
+        .zip(&b[i..len])
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gens::{builders::NaiveBuilder, IndexMode, StrIndex},
+        SuffixArray,
+    };
+
+    fn naive_lcp<M>(sa: &SuffixArray<&str, u8, M>) -> Vec<usize> {
+        let values = sa.values().as_bytes();
+        let mut lcp = vec![0; sa.indices().len()];
+        for (i, w) in sa.indices().windows(2).enumerate() {
+            lcp[i + 1] = values[w[0]..]
+                .iter()
+                .zip(&values[w[1]..])
+                .take_while(|(a, b)| a == b)
+                .count();
+        }
+        lcp
+    }
+
+    fn check<M: IndexMode<u8>>(value: &'static str, mode: M) {
+        let sa = SuffixArray::new_by::<NaiveBuilder>(value, mode);
+        let expected = naive_lcp(&sa);
+        assert_eq!(sa.lcp().as_slice(), expected, "{value}");
+        assert_eq!(sa.lcp_u8().as_slice(), expected, "{value}");
+    }
+
+    #[test]
+    fn test_lcp() {
+        for value in [
+            "",
+            "a",
+            "aaaaaaaaaaaaaaaaaaaaaaa",
+            "abcabcabcabcabcabcabcabx",
+            "mississippi",
+            "abcde錆さびacad",
+            "錆さび錆さび錆さびさびさびabab錆",
+            "xsijecvmbnxqynqpguzombqufmwugoayupbzawgymdtqqtojgydgbcdnqsuvvdzsawcyyevwtvadjaoqagoiceparehcixtnrglh",
+        ] {
+            check(value, ());
+            check(value, StrIndex);
+        }
+    }
+
+    #[test]
+    fn test_common_prefix_u8() {
+        let a = b"0123456789abcdefghij";
+        let b = b"0123456789abcdefgh_j";
+        assert_eq!(super::common_prefix_u8(a, b), 18);
+        assert_eq!(super::common_prefix_u8(a, &b[..5]), 5);
+        assert_eq!(super::common_prefix_u8(b"x", b"y"), 0);
+    }
+}
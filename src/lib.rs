@@ -1,28 +1,65 @@
 use std::marker::PhantomData;
+pub mod append;
+pub mod bwt;
+pub mod esa;
+pub mod fm;
 pub mod gens;
-mod search;
+pub mod gsa;
+pub mod indices;
+pub mod isa;
+pub mod lcp;
+pub mod persist;
+pub mod repeats;
+pub mod sampled;
+pub mod search;
+
+use indices::IndexStorage;
 
 /// Simple suffix array
-pub struct SuffixArray<B, T = u8, M = ()> {
+#[derive(Debug, Clone)]
+pub struct SuffixArray<B, T = u8, M = (), I = Vec<usize>> {
     values: B,
-    indices: Vec<usize>,
+    indices: I,
     mode: M,
     value_type: PhantomData<T>,
 }
 
-impl<T, B, M> SuffixArray<B, T, M>
+impl<T, B, M, I> SuffixArray<B, T, M, I>
 where
     T: Ord,
     B: AsRef<[T]>,
+    I: IndexStorage,
 {
+    /// Create a [`SuffixArray`] from the values and already built indices.
+    ///
+    /// `indices` is not checked, it should be the sorted suffixes of `values` kept by `mode`.
+    pub fn from_parts(values: B, indices: I, mode: M) -> Self {
+        Self {
+            values,
+            indices,
+            mode,
+            value_type: PhantomData,
+        }
+    }
+
+    /// Split into the values, the indices and the mode.
+    pub fn into_parts(self) -> (B, I, M) {
+        (self.values, self.indices, self.mode)
+    }
+
     /// Get a reference to the suffix array's mode.
     pub fn mode(&self) -> &M {
         &self.mode
     }
 
     /// Get a reference to the suffix array's indices.
-    pub fn indices(&self) -> &[usize] {
-        self.indices.as_ref()
+    pub fn indices(&self) -> &[I::Index] {
+        self.indices.as_slice()
+    }
+
+    /// Get a reference to the suffix array's index storage.
+    pub fn index_storage(&self) -> &I {
+        &self.indices
     }
 
     /// Get a reference to the suffix array's values.
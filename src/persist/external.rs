@@ -0,0 +1,486 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    hash::Hasher,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    iter,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{encode_indices, ElementTag, Fnv64, Header, ModeTag, PersistError, CHUNK, VERSION};
+use crate::{gens::IndexMode, indices::SaIndex};
+
+/// Builder writing the persisted indices of `u8` values read from a reader,
+/// keeping only a run of entries in memory.
+///
+/// The values are streamed once, and the suffixes are sorted by prefix doubling on disk:
+/// each round sorts the positions by the names of their prefixes of length `h` and `h` ahead
+/// in runs of [`run_len`](`ExternalBuilder::run_len`) entries spilled to files in
+/// the temporary directory, and names them by their prefixes of length `2h`.
+/// It takes `O(log L)` rounds for the longest repeated prefix `L`, each sorting entries of
+/// 24 bytes per value, and at most two copies of them are on disk at a time.
+/// The runs are merged at most 128 at a time, in more passes if there are more of them.
+/// Neither the values nor the suffixes are held in memory.
+/// The result is written in the format of [`SuffixArray::write_to`](`crate::SuffixArray::write_to`),
+/// and can be opened by [`SuffixArray::read_from`](`crate::SuffixArray::read_from`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExternalBuilder {
+    temp_dir: PathBuf,
+    run_len: usize,
+}
+
+/// Maximum number of the runs merged at once, each open with a buffer.
+const MAX_RUNS: usize = 128;
+
+/// Number of the temporary files created in this process, to name them.
+static FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl ExternalBuilder {
+    /// Create a builder spilling the runs to `temp_dir`, which should exist.
+    pub fn new(temp_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            temp_dir: temp_dir.into(),
+            run_len: 1 << 22,
+        }
+    }
+
+    /// Set the number of the entries sorted in memory at once.
+    ///
+    /// # Panics
+    /// Panics if `run_len` is `0`.
+    pub fn run_len(mut self, run_len: usize) -> Self {
+        assert_ne!(run_len, 0);
+        self.run_len = run_len;
+        self
+    }
+
+    /// Read the values from `reader`, and write the indices built with `mode` to `writer`
+    /// in [`SaIndex::WIDTH`] bytes of `X`.
+    ///
+    /// The header is written first with the checksums left out, and rewritten
+    /// by seeking back after the indices.
+    ///
+    /// Returns the written [`Header`].
+    pub fn build<R, W, M, X>(&self, reader: R, mode: M, writer: W) -> Result<Header, PersistError>
+    where
+        R: Read,
+        W: Write + Seek,
+        M: IndexMode<u8> + ModeTag,
+        X: SaIndex,
+    {
+        let mut text_hasher = Fnv64::default();
+        let mut len = 0;
+        let mut values = BufReader::new(reader).bytes().peekable();
+        let mut initial = iter::from_fn(|| {
+            // the values past the width are only counted.
+            if !X::fits(len as usize + 1) {
+                return None;
+            }
+            let value = match values.next()? {
+                Ok(value) => value,
+                Err(e) => return Some(Err(e)),
+            };
+            text_hasher.write_u8(value);
+            let pos = len;
+            len += 1;
+            // an error reading the next value is returned by the next call.
+            let next = match values.peek() {
+                Some(Ok(next)) => name_of(*next),
+                _ => 0,
+            };
+            Some(Ok(Entry {
+                name: name_of(value),
+                next,
+                pos,
+                kept: !mode.need_check() || mode.is_index(pos as usize, &value),
+            }))
+        });
+        let runs = self.runs(&mut initial, Entry::pair)?;
+        let mut len = len as usize;
+        for value in values {
+            value?;
+            len += 1;
+        }
+        if !X::fits(len) {
+            return Err(PersistError::TextTooLong {
+                len: len as u64,
+                width: X::WIDTH as u8,
+            });
+        }
+        let mut sorted = self.merge(runs, Entry::pair)?;
+
+        // `h` is the length of the prefixes named by the sorted pairs.
+        let mut h = 2;
+        let order = loop {
+            // each name is the rank of the first of the equal pairs, written in their order.
+            let (named, mut file) = self.temp_file()?;
+            let mut distinct = 0;
+            let mut last = None;
+            let mut name = 0;
+            for (rank, entry) in sorted.enumerate() {
+                let entry = entry?;
+                if last != Some(entry.pair()) {
+                    last = Some(entry.pair());
+                    name = rank as u64 + 1;
+                    distinct += 1;
+                }
+                Entry {
+                    name,
+                    next: 0,
+                    ..entry
+                }
+                .write_to(&mut file)?;
+            }
+            file.flush()?;
+            drop(file);
+            if distinct == len {
+                // the names are the ranks of the suffixes, in order.
+                break named;
+            }
+            let by_pos = self.sort(named.entries()?, |e| e.pos)?;
+            drop(named);
+            let names = self.spill(by_pos)?;
+            sorted = self.sort(names.pairs(h)?, Entry::pair)?;
+            h *= 2;
+        };
+
+        let mut header = Header {
+            version: VERSION,
+            index_width: X::WIDTH as u8,
+            element_type: u8::TAG.to_string(),
+            index_mode: M::TAG.to_string(),
+            text_len: len as u64,
+            text_checksum: text_hasher.finish(),
+            index_count: 0,
+            index_checksum: 0,
+        };
+        let mut writer = BufWriter::new(writer);
+        let start = writer.stream_position()?;
+        header.write_to(&mut writer)?;
+        let mut index_hasher = Fnv64::default();
+        let mut chunk = Vec::with_capacity(CHUNK);
+        let mut write_chunk = |chunk: &mut Vec<X>, writer: &mut BufWriter<W>| {
+            let entries = encode_indices(chunk);
+            index_hasher.write(&entries);
+            chunk.clear();
+            writer.write_all(&entries)
+        };
+        for entry in order.entries()? {
+            let entry = entry?;
+            if entry.kept {
+                chunk.push(X::from_usize(entry.pos as usize).unwrap());
+                header.index_count += 1;
+                if chunk.len() == CHUNK {
+                    write_chunk(&mut chunk, &mut writer)?;
+                }
+            }
+        }
+        write_chunk(&mut chunk, &mut writer)?;
+        header.index_checksum = index_hasher.finish();
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(start))?;
+        header.write_to(&mut writer)?;
+        writer.seek(SeekFrom::Start(end))?;
+        writer.flush()?;
+        Ok(header)
+    }
+
+    /// Create a file in the temporary directory.
+    fn temp_file(&self) -> io::Result<(TempFile, BufWriter<File>)> {
+        let path = self.temp_dir.join(format!(
+            "bd-suffix-{}-{}.run",
+            std::process::id(),
+            FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::create(&path)?;
+        Ok((TempFile { path }, BufWriter::new(file)))
+    }
+
+    /// Write the entries of a fresh `merge` in order to a file, or take its only run.
+    fn spill<K: Ord>(&self, mut merge: Merge<K>) -> io::Result<TempFile> {
+        if merge.runs.len() == 1 {
+            return Ok(merge.runs.pop().unwrap().0);
+        }
+        let (path, mut file) = self.temp_file()?;
+        for entry in merge {
+            entry?.write_to(&mut file)?;
+        }
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Sort `entries` by `key` in runs of [`run_len`](`ExternalBuilder::run_len`) entries,
+    /// and merge the runs.
+    fn sort<K: Ord>(
+        &self,
+        entries: impl Iterator<Item = io::Result<Entry>>,
+        key: fn(&Entry) -> K,
+    ) -> io::Result<Merge<K>> {
+        let runs = self.runs(entries, key)?;
+        self.merge(runs, key)
+    }
+
+    /// Sort `entries` by `key` in runs of [`run_len`](`ExternalBuilder::run_len`) entries
+    /// written to files.
+    fn runs<K: Ord>(
+        &self,
+        mut entries: impl Iterator<Item = io::Result<Entry>>,
+        key: fn(&Entry) -> K,
+    ) -> io::Result<Vec<TempFile>> {
+        let mut runs = vec![];
+        let mut run = vec![];
+        loop {
+            run.clear();
+            for entry in entries.by_ref().take(self.run_len) {
+                run.push(entry?);
+            }
+            if run.is_empty() {
+                break;
+            }
+            run.sort_unstable_by_key(key);
+            let (path, mut file) = self.temp_file()?;
+            for entry in &run {
+                entry.write_to(&mut file)?;
+            }
+            file.flush()?;
+            runs.push(path);
+        }
+        Ok(runs)
+    }
+
+    /// Merge the runs sorted by `key`, merging [`MAX_RUNS`] of them at a time to files
+    /// until there are no more, so that few files are open at once.
+    fn merge<K: Ord>(&self, mut runs: Vec<TempFile>, key: fn(&Entry) -> K) -> io::Result<Merge<K>> {
+        while runs.len() > MAX_RUNS {
+            let mut rest = runs.into_iter();
+            runs = vec![];
+            loop {
+                let group = rest.by_ref().take(MAX_RUNS).collect::<Vec<_>>();
+                if group.is_empty() {
+                    break;
+                }
+                runs.push(self.spill(Merge::new(group, key)?)?);
+            }
+        }
+        Merge::new(runs, key)
+    }
+}
+
+/// Name of a value as a prefix of length 1, leaving `0` past the end.
+#[inline]
+fn name_of(value: u8) -> u64 {
+    value as u64 + 1
+}
+
+/// Entry of the temporary files, a suffix with the names of its prefix and the next one.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// name of the prefix of the suffix from 1, equal for the equal prefixes
+    name: u64,
+    /// name of the prefix of the same length after it, or `0` past the end
+    next: u64,
+    pos: u64,
+    /// whether the mode keeps the suffix
+    kept: bool,
+}
+
+impl Entry {
+    const LEN: usize = 24;
+    /// Bit of the position recording `kept` in the files.
+    const KEPT: u64 = 1 << 63;
+
+    #[inline]
+    fn pair(&self) -> (u64, u64) {
+        (self.name, self.next)
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut buf = [0; Self::LEN];
+        buf[..8].copy_from_slice(&self.name.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.next.to_le_bytes());
+        let pos = if self.kept {
+            self.pos | Self::KEPT
+        } else {
+            self.pos
+        };
+        buf[16..].copy_from_slice(&pos.to_le_bytes());
+        writer.write_all(&buf)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut buf = [0; Self::LEN];
+        match reader.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let word = |i: usize| u64::from_le_bytes(buf[i * 8..(i + 1) * 8].try_into().unwrap());
+        Ok(Some(Self {
+            name: word(0),
+            next: word(1),
+            pos: word(2) & !Self::KEPT,
+            kept: word(2) & Self::KEPT != 0,
+        }))
+    }
+}
+
+/// File removed on drop.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn open(&self) -> io::Result<BufReader<File>> {
+        File::open(&self.path).map(BufReader::new)
+    }
+
+    /// The entries in the order written.
+    fn entries(&self) -> io::Result<impl Iterator<Item = io::Result<Entry>>> {
+        let mut entries = self.open()?;
+        Ok(iter::from_fn(move || {
+            Entry::read_from(&mut entries).transpose()
+        }))
+    }
+
+    /// The entries sorted by the position with the name of the prefix `h` ahead.
+    fn pairs(&self, h: usize) -> io::Result<impl Iterator<Item = io::Result<Entry>>> {
+        let mut entries = self.open()?;
+        let mut ahead = File::open(&self.path)?;
+        ahead.seek(SeekFrom::Start((h * Entry::LEN) as u64))?;
+        let mut ahead = BufReader::new(ahead);
+        Ok(iter::from_fn(move || {
+            let entry = Entry::read_from(&mut entries).transpose()?;
+            Some(entry.and_then(|entry| {
+                let next = Entry::read_from(&mut ahead)?.map_or(0, |e| e.name);
+                Ok(Entry { next, ..entry })
+            }))
+        }))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Merge of the sorted runs, removing them when dropped.
+struct Merge<K> {
+    runs: Vec<(TempFile, BufReader<File>)>,
+    /// the head of each run
+    heads: Vec<Option<Entry>>,
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    key: fn(&Entry) -> K,
+}
+
+impl<K: Ord> Merge<K> {
+    fn new(runs: Vec<TempFile>, key: fn(&Entry) -> K) -> io::Result<Self> {
+        let mut merge = Self {
+            runs: vec![],
+            heads: vec![],
+            heap: BinaryHeap::with_capacity(runs.len()),
+            key,
+        };
+        for (run, path) in runs.into_iter().enumerate() {
+            let mut reader = path.open()?;
+            let head = Entry::read_from(&mut reader)?;
+            if let Some(head) = &head {
+                merge.heap.push(Reverse((key(head), run)));
+            }
+            merge.heads.push(head);
+            merge.runs.push((path, reader));
+        }
+        Ok(merge)
+    }
+}
+
+impl<K: Ord> Iterator for Merge<K> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, run)) = self.heap.pop()?;
+        let entry = self.heads[run].take()?;
+        match Entry::read_from(&mut self.runs[run].1) {
+            Ok(head) => {
+                if let Some(head) = &head {
+                    self.heap.push(Reverse(((self.key)(head), run)));
+                }
+                self.heads[run] = head;
+                Some(Ok(entry))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalBuilder;
+    use crate::{gens::StrIndex, indices::U40, SuffixArray};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_external() {
+        let dir = std::env::temp_dir();
+        let text = "abracadabra錆さび錆さびさびmississippi".repeat(7);
+        for run_len in [1, 5, 64, 10000] {
+            let builder = ExternalBuilder::new(&dir).run_len(run_len);
+            let mut buf = Cursor::new(vec![]);
+            builder
+                .build::<_, _, _, u32>(text.as_bytes(), StrIndex, &mut buf)
+                .unwrap();
+            let loaded = SuffixArray::<_, _, _>::read_from(
+                text.as_str(),
+                StrIndex,
+                buf.into_inner().as_slice(),
+            )
+            .unwrap();
+            let expected = SuffixArray::<_, u8, StrIndex>::new(text.as_str());
+            assert_eq!(loaded.indices(), expected.indices());
+        }
+        let mut buf = Cursor::new(vec![]);
+        let header = ExternalBuilder::new(&dir)
+            .build::<_, _, _, U40>(&b""[..], (), &mut buf)
+            .unwrap();
+        assert_eq!(header.index_count(), 0);
+    }
+
+    #[test]
+    fn test_external_many_runs() {
+        // more runs than merged at once.
+        let text = (0..3000u32)
+            .map(|i| (b'a' + (i * i % 7) as u8) as char)
+            .collect::<String>();
+        let mut buf = Cursor::new(vec![]);
+        ExternalBuilder::new(std::env::temp_dir())
+            .run_len(2)
+            .build::<_, _, _, u32>(text.as_bytes(), (), &mut buf)
+            .unwrap();
+        let loaded =
+            SuffixArray::<_, _, _>::read_from(text.as_str(), (), buf.into_inner().as_slice())
+                .unwrap();
+        let expected = SuffixArray::<_, u8, ()>::new(text.as_str());
+        assert_eq!(loaded.indices(), expected.indices());
+    }
+
+    #[test]
+    fn test_external_repetitive() {
+        let dir = std::env::temp_dir();
+        let text = "abcab".repeat(2000);
+        // the header is rewritten where the writer started.
+        let mut buf = Cursor::new(b"head".to_vec());
+        buf.set_position(4);
+        let header = ExternalBuilder::new(&dir)
+            .run_len(1000)
+            .build::<_, _, _, u32>(text.as_bytes(), (), &mut buf)
+            .unwrap();
+        assert_eq!(header.index_count(), text.len() as u64);
+        let buf = buf.into_inner();
+        assert_eq!(&buf[..4], b"head");
+        let loaded = SuffixArray::<_, _, _>::read_from(text.as_str(), (), &buf[4..]).unwrap();
+        let expected = SuffixArray::<_, u8, ()>::new(text.as_str());
+        assert_eq!(loaded.indices(), expected.indices());
+    }
+}
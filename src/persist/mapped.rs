@@ -0,0 +1,209 @@
+use std::{hash::Hasher, marker::PhantomData, mem::size_of};
+
+use super::{ElementTag, Fnv64, Header, ModeTag, PersistError};
+use crate::{
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex, U40},
+    SuffixArray,
+};
+
+/// [`SaIndex`] which can be read in place from the persisted bytes.
+///
+/// # Safety
+/// Every bit pattern of [`SaIndex::WIDTH`] bytes must be a valid value,
+/// and the memory layout must be the little endian encoding of the value
+/// if [`in_place`](`MappableIndex::in_place`) returns true.
+pub unsafe trait MappableIndex: SaIndex {
+    /// Returns true if the current target stores this type as written in the file.
+    #[inline]
+    fn in_place() -> bool {
+        cfg!(target_endian = "little") && size_of::<Self>() == Self::WIDTH
+    }
+}
+
+unsafe impl MappableIndex for usize {}
+unsafe impl MappableIndex for u32 {}
+unsafe impl MappableIndex for u64 {}
+unsafe impl MappableIndex for U40 {
+    #[inline]
+    fn in_place() -> bool {
+        true
+    }
+}
+
+/// Indices read in place from the bytes written by [`SuffixArray::write_to`], without copying.
+///
+/// `S` is usually a memory-mapped file (e.g. `memmap2::Mmap`), so loading a large index
+/// doesn't need to read it into a new [`Vec`].
+/// The entries are used as they are, so `X` must have the width written in the file,
+/// the target must be little endian for the multi-byte integers,
+/// and the bytes must be aligned for `X` (which is the case for memory-mapped files).
+#[derive(Debug, Clone)]
+pub struct MappedIndices<S, X = usize> {
+    bytes: S,
+    offset: usize,
+    count: usize,
+    index_type: PhantomData<X>,
+}
+
+impl<S: AsRef<[u8]>, X: MappableIndex> MappedIndices<S, X> {
+    /// Read the [`Header`] of `bytes` and check the layout of the indices.
+    ///
+    /// The indices themselves are not checked here.
+    pub fn new(bytes: S) -> Result<(Self, Header), PersistError> {
+        let header = Header::read_from(bytes.as_ref())?;
+        if header.index_width() as usize != X::WIDTH || !X::in_place() {
+            return Err(PersistError::UnsupportedIndexWidth(header.index_width()));
+        }
+        let offset = header.encoded_len();
+        let count = header.index_count() as usize;
+        let body = count
+            .checked_mul(X::WIDTH)
+            .and_then(|len| bytes.as_ref().get(offset..)?.get(..len))
+            .ok_or(PersistError::InvalidHeader)?;
+        if body.as_ptr().align_offset(std::mem::align_of::<X>()) != 0 {
+            return Err(PersistError::Misaligned);
+        }
+        Ok((
+            Self {
+                bytes,
+                offset,
+                count,
+                index_type: PhantomData,
+            },
+            header,
+        ))
+    }
+
+    /// Get the encoded indices.
+    fn body(&self) -> &[u8] {
+        &self.bytes.as_ref()[self.offset..self.offset + self.count * X::WIDTH]
+    }
+
+    /// Convert into the inner bytes.
+    pub fn into_inner(self) -> S {
+        self.bytes
+    }
+}
+
+impl<S: AsRef<[u8]>, X: MappableIndex> IndexStorage for MappedIndices<S, X> {
+    type Index = X;
+
+    #[inline]
+    fn as_slice(&self) -> &[X] {
+        // SAFETY: every bit pattern is a valid `X`,
+        // and `new` checked that the target reads the entries as they are written.
+        let (head, indices, tail) = unsafe { self.body().align_to::<X>() };
+        assert!(
+            head.is_empty() && tail.is_empty(),
+            "the bytes are moved to an unaligned address"
+        );
+        indices
+    }
+}
+
+impl<T, B, M, S, X> SuffixArray<B, T, M, MappedIndices<S, X>>
+where
+    T: Ord + ElementTag,
+    B: AsRef<[T]>,
+    M: IndexMode<T> + ModeTag,
+    S: AsRef<[u8]>,
+    X: MappableIndex,
+{
+    /// Use the indices written by [`write_to`](`SuffixArray::write_to`) in `bytes` without copying.
+    ///
+    /// Checks the header against `values` and `mode`, and verifies the checksums and the range of the indices.
+    /// This reads all of `values` and `bytes` once, see [`from_mapped_trusted`](`Self::from_mapped_trusted`)
+    /// to skip it.
+    pub fn from_mapped(values: B, mode: M, bytes: S) -> Result<Self, PersistError> {
+        let (indices, header) = MappedIndices::<S, X>::new(bytes)?;
+        header.check::<T, M>(values.as_ref())?;
+        let mut index_hasher = Fnv64::default();
+        index_hasher.write(indices.body());
+        if index_hasher.finish() != header.index_checksum() {
+            return Err(PersistError::IndexChecksumMismatch);
+        }
+        let len = values.as_ref().len();
+        if let Some(index) = indices.as_slice().iter().find(|i| i.to_usize() >= len) {
+            return Err(PersistError::IndexOutOfRange(index.to_usize() as u64));
+        }
+        Ok(Self::from_parts(values, indices, mode))
+    }
+
+    /// Use the indices written by [`write_to`](`SuffixArray::write_to`) in `bytes` without copying,
+    /// checking only the header against the element type, `mode` and the length of `values`.
+    ///
+    /// Broken indices don't cause undefined behavior, but searches may panic or return wrong results.
+    pub fn from_mapped_trusted(values: B, mode: M, bytes: S) -> Result<Self, PersistError> {
+        let (indices, header) = MappedIndices::<S, X>::new(bytes)?;
+        header.check_identity::<T, M>(values.as_ref().len())?;
+        Ok(Self::from_parts(values, indices, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MappedIndices;
+    use crate::{gens::StrIndex, indices::U40, persist::PersistError, SuffixArray};
+
+    /// Copy `bytes` to an address which is `shift` bytes after an 8 byte boundary.
+    fn copy_aligned(bytes: &[u8], shift: usize, buf: &mut Vec<u8>) -> std::ops::Range<usize> {
+        buf.clear();
+        buf.resize(bytes.len() + 16, 0);
+        let start = buf.as_ptr().align_offset(8) + shift;
+        buf[start..start + bytes.len()].copy_from_slice(bytes);
+        start..start + bytes.len()
+    }
+
+    #[test]
+    fn test_mapped() {
+        let text = "abcde錆さびacadabcde";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let mut bytes = vec![];
+        sa.write_to(&mut bytes).unwrap();
+        let mut buf = vec![];
+        let range = copy_aligned(&bytes, 0, &mut buf);
+        let mapped: SuffixArray<_, _, _, MappedIndices<_>> =
+            SuffixArray::from_mapped(text, StrIndex, &buf[range.clone()]).unwrap();
+        assert_eq!(mapped.indices(), sa.indices());
+        assert_eq!(
+            mapped.search_naive("bc").map(|r| r.len()),
+            sa.search_naive("bc").map(|r| r.len())
+        );
+        let trusted: SuffixArray<_, _, _, MappedIndices<_>> =
+            SuffixArray::from_mapped_trusted(text, StrIndex, &buf[range]).unwrap();
+        assert_eq!(trusted.indices(), sa.indices());
+        assert!(matches!(
+            SuffixArray::<_, _, _, MappedIndices<_, u32>>::from_mapped(
+                text,
+                StrIndex,
+                bytes.as_slice()
+            ),
+            Err(PersistError::UnsupportedIndexWidth(8))
+        ));
+
+        let range = copy_aligned(&bytes, 1, &mut buf);
+        assert!(matches!(
+            SuffixArray::<_, _, _, MappedIndices<_>>::from_mapped(text, StrIndex, &buf[range]),
+            Err(PersistError::Misaligned)
+        ));
+    }
+
+    #[test]
+    fn test_mapped_narrow() {
+        let text = "abcde錆さびacadabcde";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let narrow = SuffixArray::<_, u8, StrIndex>::new(text)
+            .with_index_width::<U40>()
+            .unwrap();
+        let mut bytes = vec![];
+        narrow.write_to(&mut bytes).unwrap();
+        // U40 is aligned to 1
+        let mapped =
+            SuffixArray::<_, _, _, MappedIndices<_, U40>>::from_mapped(text, StrIndex, &bytes[..])
+                .unwrap();
+        assert_eq!(mapped.indices(), narrow.indices());
+        let widened = SuffixArray::<_, _, _>::read_from(text, StrIndex, bytes.as_slice()).unwrap();
+        assert_eq!(widened.indices(), sa.indices());
+    }
+}
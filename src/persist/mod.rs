@@ -0,0 +1,575 @@
+//! Binary format to persist the indices of a built [`SuffixArray`].
+//!
+//! Only the indices are stored. The values are supplied again at [`read_from`](`SuffixArray::read_from`),
+//! and checked against the text length and checksum recorded in the [`Header`].
+//!
+//! All integers are little endian.
+//!
+//! | field            | size                                  |
+//! |------------------|---------------------------------------|
+//! | magic `b"BDSA"`  | 4                                     |
+//! | version          | 2                                     |
+//! | index width      | 1                                     |
+//! | reserved         | 1                                     |
+//! | element type     | 4 (length) + name                     |
+//! | index mode       | 4 (length) + tag                      |
+//! | text length      | 8                                     |
+//! | text checksum    | 8                                     |
+//! | index count      | 8                                     |
+//! | index checksum   | 8                                     |
+//! | padding          | to a multiple of 8 bytes              |
+//! | indices          | index count * index width             |
+//!
+//! The element type is recorded by its [`ElementTag::TAG`],
+//! and the index mode by its [`ModeTag::TAG`].
+//! The names are at most [`MAX_NAME_LEN`] bytes.
+//! Checksums are 64 bit FNV-1a, of the values in the little endian encoding of [`ElementTag`].
+
+use std::{
+    fmt,
+    hash::Hasher,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use crate::{
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+mod external;
+mod mapped;
+pub use external::*;
+pub use mapped::*;
+
+/// Magic bytes at the head of the format.
+pub const MAGIC: [u8; 4] = *b"BDSA";
+/// Current version of the format.
+///
+/// Version 1 recorded the index mode by its type name.
+pub const VERSION: u16 = 2;
+/// Max length of the names in the [`Header`].
+pub const MAX_NAME_LEN: usize = 1024;
+
+/// Stable name of an [`IndexMode`] recorded in the [`Header`].
+///
+/// The tag must not change across versions of the program, or the files written before are rejected.
+pub trait ModeTag {
+    /// Name of the mode.
+    const TAG: &'static str;
+}
+
+impl ModeTag for () {
+    const TAG: &'static str = "all";
+}
+
+impl ModeTag for crate::gens::StrIndex {
+    const TAG: &'static str = "str";
+}
+
+/// Stable name and encoding of the element type recorded in the [`Header`].
+///
+/// The tag and the encoding must not change across versions of the program or platforms,
+/// or the files written before are rejected.
+pub trait ElementTag {
+    /// Name of the element type.
+    const TAG: &'static str;
+
+    /// Write the value to the checksum in little endian.
+    fn write_le<H: Hasher>(&self, hasher: &mut H);
+}
+
+macro_rules! impl_element_tag {
+    ($($t:ty => $encoded:ty),* $(,)?) => {
+        $(
+            impl ElementTag for $t {
+                const TAG: &'static str = stringify!($t);
+
+                #[inline]
+                fn write_le<H: Hasher>(&self, hasher: &mut H) {
+                    hasher.write(&(*self as $encoded).to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+// `usize` and `isize` are encoded in 64 bits on every platform.
+impl_element_tag!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => u64,
+    i8 => i8, i16 => i16, i32 => i32, i64 => i64, i128 => i128, isize => i64,
+    char => u32, bool => u8,
+);
+
+/// Header of the persisted indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Header {
+    version: u16,
+    index_width: u8,
+    element_type: String,
+    index_mode: String,
+    text_len: u64,
+    text_checksum: u64,
+    index_count: u64,
+    index_checksum: u64,
+}
+
+impl Header {
+    /// Version of the format.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Bytes per index entry.
+    pub fn index_width(&self) -> u8 {
+        self.index_width
+    }
+
+    /// [`ElementTag`] of the element type of the values.
+    pub fn element_type(&self) -> &str {
+        &self.element_type
+    }
+
+    /// [`ModeTag`] of the [`IndexMode`] used to build the indices.
+    pub fn index_mode(&self) -> &str {
+        &self.index_mode
+    }
+
+    /// Length of the values.
+    pub fn text_len(&self) -> u64 {
+        self.text_len
+    }
+
+    /// Checksum of the values.
+    pub fn text_checksum(&self) -> u64 {
+        self.text_checksum
+    }
+
+    /// Number of the indices.
+    pub fn index_count(&self) -> u64 {
+        self.index_count
+    }
+
+    /// Checksum of the encoded indices.
+    pub fn index_checksum(&self) -> u64 {
+        self.index_checksum
+    }
+
+    /// Size of the encoded header including the padding.
+    pub fn encoded_len(&self) -> usize {
+        let len = 4 + 2 + 1 + 1 + 4 + self.element_type.len() + 4 + self.index_mode.len() + 8 * 4;
+        len.next_multiple_of(8)
+    }
+
+    /// Write the header and the padding.
+    pub(crate) fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.push(self.index_width);
+        buf.push(0);
+        for name in [&self.element_type, &self.index_mode] {
+            buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+        }
+        for v in [
+            self.text_len,
+            self.text_checksum,
+            self.index_count,
+            self.index_checksum,
+        ] {
+            buf.extend_from_slice(&v.to_le_bytes());
+        }
+        buf.resize(self.encoded_len(), 0);
+        writer.write_all(&buf)
+    }
+
+    /// Read the header and skip the padding.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, PersistError> {
+        let mut fixed = [0; 8];
+        reader.read_exact(&mut fixed)?;
+        if fixed[..4] != MAGIC {
+            return Err(PersistError::InvalidMagic);
+        }
+        let version = u16::from_le_bytes([fixed[4], fixed[5]]);
+        if version != VERSION {
+            return Err(PersistError::UnsupportedVersion(version));
+        }
+        let index_width = fixed[6];
+        let mut read_name = || -> Result<String, PersistError> {
+            let len = read_u32(&mut reader)? as usize;
+            if len > MAX_NAME_LEN {
+                return Err(PersistError::InvalidHeader);
+            }
+            let mut name = vec![0; len];
+            reader.read_exact(&mut name)?;
+            String::from_utf8(name).map_err(|_| PersistError::InvalidHeader)
+        };
+        let element_type = read_name()?;
+        let index_mode = read_name()?;
+        let header = Self {
+            version,
+            index_width,
+            element_type,
+            index_mode,
+            text_len: read_u64(&mut reader)?,
+            text_checksum: read_u64(&mut reader)?,
+            index_count: read_u64(&mut reader)?,
+            index_checksum: read_u64(&mut reader)?,
+        };
+        let unpadded = 8 + 8 + header.element_type.len() + header.index_mode.len() + 8 * 4;
+        let mut padding = [0; 8];
+        reader.read_exact(&mut padding[..header.encoded_len() - unpadded])?;
+        Ok(header)
+    }
+
+    /// Check that the indices are built from `values` by the mode `M`.
+    pub(crate) fn check<T, M>(&self, values: &[T]) -> Result<(), PersistError>
+    where
+        T: ElementTag,
+        M: ModeTag,
+    {
+        self.check_identity::<T, M>(values.len())?;
+        if self.text_checksum != text_checksum(values) {
+            return Err(PersistError::TextChecksumMismatch);
+        }
+        Ok(())
+    }
+
+    /// Check the element type, the mode and the length of the values.
+    pub(crate) fn check_identity<T: ElementTag, M: ModeTag>(
+        &self,
+        len: usize,
+    ) -> Result<(), PersistError> {
+        if self.element_type != T::TAG {
+            return Err(PersistError::ElementTypeMismatch {
+                expected: T::TAG.to_string(),
+                found: self.element_type.clone(),
+            });
+        }
+        if self.index_mode != M::TAG {
+            return Err(PersistError::IndexModeMismatch {
+                expected: M::TAG.to_string(),
+                found: self.index_mode.clone(),
+            });
+        }
+        if self.text_len != len as u64 {
+            return Err(PersistError::TextLengthMismatch {
+                expected: len as u64,
+                found: self.text_len,
+            });
+        }
+        if self.index_count > self.text_len {
+            return Err(PersistError::InvalidHeader);
+        }
+        Ok(())
+    }
+}
+
+/// Error on reading or writing the persisted indices.
+#[derive(Debug)]
+pub enum PersistError {
+    /// IO error of the reader.
+    Io(io::Error),
+    /// The data doesn't start with [`MAGIC`].
+    InvalidMagic,
+    /// The version is not supported by this build.
+    UnsupportedVersion(u16),
+    /// The header is broken.
+    InvalidHeader,
+    /// The index width is not supported.
+    UnsupportedIndexWidth(u8),
+    /// The element type is different from the values.
+    ElementTypeMismatch { expected: String, found: String },
+    /// The index mode is different from the supplied one.
+    IndexModeMismatch { expected: String, found: String },
+    /// The length of the values is different from the recorded one.
+    TextLengthMismatch { expected: u64, found: u64 },
+    /// The checksum of the values is different from the recorded one.
+    TextChecksumMismatch,
+    /// The checksum of the indices is different from the recorded one.
+    IndexChecksumMismatch,
+    /// Some index points outside of the values.
+    IndexOutOfRange(u64),
+    /// The indices are not aligned to be used in place.
+    Misaligned,
+    /// The values are too long for the index width.
+    TextTooLong { len: u64, width: u8 },
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {e}"),
+            Self::InvalidMagic => f.write_str("invalid magic bytes"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported version {v}"),
+            Self::InvalidHeader => f.write_str("invalid header"),
+            Self::UnsupportedIndexWidth(w) => write!(f, "unsupported index width {w}"),
+            Self::ElementTypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "element type mismatch: expected {expected}, found {found}"
+                )
+            }
+            Self::IndexModeMismatch { expected, found } => {
+                write!(f, "index mode mismatch: expected {expected}, found {found}")
+            }
+            Self::TextLengthMismatch { expected, found } => {
+                write!(
+                    f,
+                    "text length mismatch: expected {expected}, found {found}"
+                )
+            }
+            Self::TextChecksumMismatch => f.write_str("text checksum mismatch"),
+            Self::IndexChecksumMismatch => f.write_str("index checksum mismatch"),
+            Self::IndexOutOfRange(i) => write!(f, "index {i} is out of range"),
+            Self::Misaligned => f.write_str("indices are not aligned"),
+            Self::TextTooLong { len, width } => {
+                write!(f, "text length {len} is too long for index width {width}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PersistError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PersistError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// 64 bit FNV-1a hasher.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Fnv64(u64);
+
+impl Default for Fnv64 {
+    #[inline]
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Checksum of the values, hashing the encoding of each element in order.
+pub(crate) fn text_checksum<T: ElementTag>(values: &[T]) -> u64 {
+    let mut hasher = Fnv64::default();
+    for v in values {
+        v.write_le(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Entries encoded at once.
+const CHUNK: usize = 1024;
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord + ElementTag,
+    B: AsRef<[T]>,
+    M: IndexMode<T> + ModeTag,
+    I: IndexStorage,
+{
+    /// Write the indices with a [`Header`] to `writer`.
+    ///
+    /// Each entry is written in [`SaIndex::WIDTH`] bytes of the index type.
+    /// The values are not written. See the [module docs](`crate::persist`) for the format.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let values = self.values.as_ref();
+        let indices = self.indices();
+        let mut index_hasher = Fnv64::default();
+        for chunk in indices.chunks(CHUNK) {
+            index_hasher.write(&encode_indices(chunk));
+        }
+        let header = Header {
+            version: VERSION,
+            index_width: I::Index::WIDTH as u8,
+            element_type: T::TAG.to_string(),
+            index_mode: M::TAG.to_string(),
+            text_len: values.len() as u64,
+            text_checksum: text_checksum(values),
+            index_count: indices.len() as u64,
+            index_checksum: index_hasher.finish(),
+        };
+        header.write_to(&mut writer)?;
+        for chunk in indices.chunks(CHUNK) {
+            writer.write_all(&encode_indices(chunk))?;
+        }
+        writer.flush()
+    }
+}
+
+impl<T, B, M, X> SuffixArray<B, T, M, Vec<X>>
+where
+    T: Ord + ElementTag,
+    B: AsRef<[T]>,
+    M: IndexMode<T> + ModeTag,
+    X: SaIndex,
+{
+    /// Read the indices written by [`write_to`](`SuffixArray::write_to`) for `values` built with `mode`.
+    ///
+    /// The entries can be written in another width than `X`, if every index fits in `X`.
+    /// Fails if the header doesn't match `values` and `mode`, or the indices are broken.
+    pub fn read_from<R: Read>(values: B, mode: M, mut reader: R) -> Result<Self, PersistError> {
+        let header = Header::read_from(&mut reader)?;
+        header.check::<T, M>(values.as_ref())?;
+        let width = header.index_width as usize;
+        if !(1..=8).contains(&width) {
+            return Err(PersistError::UnsupportedIndexWidth(header.index_width));
+        }
+        let len = values.as_ref().len() as u64;
+        let count = header.index_count as usize;
+        let mut indices = Vec::with_capacity(count);
+        let mut index_hasher = Fnv64::default();
+        let mut buf = vec![0; CHUNK * width];
+        while indices.len() < count {
+            let n = (count - indices.len()).min(CHUNK);
+            let buf = &mut buf[..n * width];
+            reader.read_exact(buf)?;
+            index_hasher.write(buf);
+            for entry in buf.chunks_exact(width) {
+                let mut bytes = [0; 8];
+                bytes[..width].copy_from_slice(entry);
+                let index = u64::from_le_bytes(bytes);
+                match X::from_usize(index as usize) {
+                    Some(x) if index < len => indices.push(x),
+                    _ => return Err(PersistError::IndexOutOfRange(index)),
+                }
+            }
+        }
+        if index_hasher.finish() != header.index_checksum {
+            return Err(PersistError::IndexChecksumMismatch);
+        }
+        Ok(Self {
+            values,
+            indices,
+            mode,
+            value_type: PhantomData,
+        })
+    }
+}
+
+/// Encode the indices in little endian, [`SaIndex::WIDTH`] bytes each.
+fn encode_indices<X: SaIndex>(indices: &[X]) -> Vec<u8> {
+    indices
+        .iter()
+        .flat_map(|i| {
+            (i.to_usize() as u64)
+                .to_le_bytes()
+                .into_iter()
+                .take(X::WIDTH)
+        })
+        .collect()
+}
+
+fn read_u32<R: Read>(mut reader: R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(mut reader: R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fnv64, Header, PersistError};
+    use crate::{gens::StrIndex, SuffixArray};
+    use std::hash::Hasher;
+
+    #[test]
+    fn test_roundtrip() {
+        let text = "abcde錆さびacadabcde";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let mut buf = vec![];
+        sa.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len() % 8, 0);
+        let loaded = SuffixArray::<_, _, _>::read_from(text, StrIndex, buf.as_slice()).unwrap();
+        assert_eq!(loaded.indices(), sa.indices());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let text = "abcde錆さびacadabcde";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let mut buf = vec![];
+        sa.write_to(&mut buf).unwrap();
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from(text, (), buf.as_slice()),
+            Err(PersistError::IndexModeMismatch { .. })
+        ));
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from("abcde錆さびacadabcdf", StrIndex, buf.as_slice()),
+            Err(PersistError::TextChecksumMismatch)
+        ));
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from("abc", StrIndex, buf.as_slice()),
+            Err(PersistError::TextLengthMismatch { .. })
+        ));
+        let chars = text.chars().collect::<Vec<_>>();
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from(chars, (), buf.as_slice()),
+            Err(PersistError::ElementTypeMismatch { .. })
+        ));
+        // swap the last two indices
+        let len = buf.len();
+        let (head, last) = buf.split_at_mut(len - 8);
+        head[len - 16..].swap_with_slice(last);
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from(text, StrIndex, buf.as_slice()),
+            Err(PersistError::IndexChecksumMismatch)
+        ));
+        let mut long_name = buf.clone();
+        long_name[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from(text, StrIndex, long_name.as_slice()),
+            Err(PersistError::InvalidHeader)
+        ));
+        buf[0] = 0;
+        assert!(matches!(
+            SuffixArray::<_, _, _>::read_from(text, StrIndex, buf.as_slice()),
+            Err(PersistError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn test_element_tag() {
+        let values = [0x0102_0304u32, 1, u32::MAX];
+        let sa = SuffixArray::new_sais(&values[..], ());
+        let mut buf = vec![];
+        sa.write_to(&mut buf).unwrap();
+        let header = Header::read_from(buf.as_slice()).unwrap();
+        assert_eq!(header.element_type(), "u32");
+        // the checksum doesn't depend on the byte order of the platform.
+        let mut hasher = Fnv64::default();
+        hasher.write(&[4, 3, 2, 1, 1, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(header.text_checksum(), hasher.finish());
+        let loaded = SuffixArray::<_, _, _>::read_from(&values[..], (), buf.as_slice()).unwrap();
+        assert_eq!(loaded.indices(), sa.indices());
+    }
+}
@@ -0,0 +1,330 @@
+//! Repeated substrings found on the [`EnhancedSuffixArray`] of a [`SuffixArray`].
+//!
+//! A repeat is maximal if it can't be extended to the left or right without losing an occurrence,
+//! and supermaximal if it is not a substring of another maximal repeat.
+//! The [`SuffixArray`] methods build the enhanced suffix array each time,
+//! the same methods of [`EnhancedSuffixArray`] reuse it.
+//!
+//! The lengths are trimmed so that a repeat ends at a position kept by the [`IndexMode`]
+//! or at the end of the values, so [`StrIndex`](`crate::gens::StrIndex`) results never split UTF-8 sequences.
+//! The mode is checked only at the end of the first occurrence, as the repeated values are same.
+
+use crate::{
+    esa::{EnhancedSuffixArray, Node},
+    gens::IndexMode,
+    indices::IndexStorage,
+    SuffixArray,
+};
+
+/// Repeated substring with its occurrences.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Repeat {
+    len: usize,
+    positions: Vec<usize>,
+}
+
+impl Repeat {
+    /// Length of the repeated substring.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the repeated substring is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Start positions of the occurrences, in ascending text order.
+    #[inline]
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    /// Get the repeated substring in `values`.
+    #[inline]
+    pub fn slice<'a, T>(&self, values: &'a [T]) -> &'a [T] {
+        let start = self.positions[0];
+        &values[start..start + self.len]
+    }
+}
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    M: IndexMode<T>,
+    I: IndexStorage,
+{
+    /// Find the longest substring occurring at least twice.
+    ///
+    /// Returns the first in suffix order if there are some, or [`None`] if nothing repeats.
+    /// See [`EnhancedSuffixArray::longest_repeated_substring`] to reuse the enhanced suffix array.
+    pub fn longest_repeated_substring(&self) -> Option<Repeat> {
+        EnhancedSuffixArray::new(self).longest_repeated_substring()
+    }
+
+    /// Find all maximal repeats of at least `min_len` values, in suffix order.
+    ///
+    /// See [`EnhancedSuffixArray::maximal_repeats`] to reuse the enhanced suffix array.
+    pub fn maximal_repeats(&self, min_len: usize) -> Vec<Repeat> {
+        EnhancedSuffixArray::new(self).maximal_repeats(min_len)
+    }
+
+    /// Find all supermaximal repeats of at least `min_len` values, in suffix order.
+    ///
+    /// See [`EnhancedSuffixArray::supermaximal_repeats`] to reuse the enhanced suffix array.
+    pub fn supermaximal_repeats(&self, min_len: usize) -> Vec<Repeat> {
+        EnhancedSuffixArray::new(self).supermaximal_repeats(min_len)
+    }
+}
+
+impl<'a, T, B, M, I> EnhancedSuffixArray<'a, B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    M: IndexMode<T>,
+    I: IndexStorage,
+{
+    /// Find the longest substring occurring at least twice, like [`SuffixArray::longest_repeated_substring`].
+    pub fn longest_repeated_substring(&self) -> Option<Repeat> {
+        let mut longest: Option<(Node, usize)> = None;
+        for node in self.nodes().filter(|node| !node.is_leaf()) {
+            let len = self.trimmed_depth(node);
+            if len > longest.map_or(0, |(_, l)| l) {
+                longest = Some((node, len));
+            }
+        }
+        longest.map(|(node, len)| repeat(self, node, len))
+    }
+
+    /// Find all maximal repeats of at least `min_len` values, in suffix order.
+    ///
+    /// The left contexts are merged from the leaves up in one pass over the child table.
+    pub fn maximal_repeats(&self, min_len: usize) -> Vec<Repeat> {
+        self.repeats_by(min_len, |_, left_diverse| left_diverse)
+    }
+
+    /// Find all supermaximal repeats of at least `min_len` values, in suffix order.
+    ///
+    /// Only the nodes whose children are all leaves can be supermaximal,
+    /// so each leaf is checked once.
+    pub fn supermaximal_repeats(&self, min_len: usize) -> Vec<Repeat> {
+        self.repeats_by(min_len, |node, _| {
+            if !self.children(node).all(|child| child.is_leaf()) {
+                return false;
+            }
+            let mut contexts = self
+                .children(node)
+                .map(|leaf| self.leaf_context(leaf))
+                .collect::<Vec<_>>();
+            contexts.sort_unstable();
+            contexts.windows(2).all(|w| w[0] != w[1])
+        })
+    }
+
+    /// Collect the right maximal repeats of the internal nodes passing `filter`,
+    /// given whether the occurrences of the node differ in their left contexts.
+    fn repeats_by<F>(&self, min_len: usize, mut filter: F) -> Vec<Repeat>
+    where
+        F: FnMut(Node, bool) -> bool,
+    {
+        let mut found = vec![];
+        self.visit_left_contexts(|node, context| {
+            let len = self.trimmed_depth(node);
+            // a trimmed repeat not longer than the parent is the repeat of an ancestor.
+            let parent_depth = self.parent(node).map_or(0, |p| p.depth());
+            if len >= min_len.max(1) && len > parent_depth && filter(node, context.is_none()) {
+                found.push((node, len));
+            }
+        });
+        // postorder to preorder, a parent starts with its first child and ends later.
+        found.sort_unstable_by_key(|(node, _)| {
+            (node.ranks().start, std::cmp::Reverse(node.ranks().end))
+        });
+        found
+            .into_iter()
+            .map(|(node, len)| repeat(self, node, len))
+            .collect()
+    }
+
+    /// Visit the internal nodes in postorder with the left context shared by their occurrences,
+    /// [`None`] if they differ or one is the first position.
+    fn visit_left_contexts<F>(&self, mut visit: F)
+    where
+        F: FnMut(Node, Option<&'a [T]>),
+    {
+        let Some(root) = self.root().filter(|root| !root.is_leaf()) else {
+            return;
+        };
+        // the node, its remaining children and the context merged from the visited ones.
+        let mut stack = vec![(root, self.children(root), None)];
+        while let Some(frame) = stack.last_mut() {
+            let context = match frame.1.next() {
+                Some(child) if !child.is_leaf() => {
+                    stack.push((child, self.children(child), None));
+                    continue;
+                }
+                Some(leaf) => self.leaf_context(leaf),
+                None => {
+                    let (node, _, context) = stack.pop().unwrap();
+                    let context = context.flatten();
+                    visit(node, context);
+                    context
+                }
+            };
+            if let Some((_, _, merged)) = stack.last_mut() {
+                *merged = Some(match *merged {
+                    None => context,
+                    Some(m) => m.filter(|m| Some(*m) == context),
+                });
+            }
+        }
+    }
+
+    /// Depth of `node` trimmed to end at an indexed position.
+    fn trimmed_depth(&self, node: Node) -> usize {
+        let sa = self.suffix_array();
+        let values = sa.values().as_ref();
+        if !sa.mode().need_check() {
+            return node.depth();
+        }
+        let start = self.positions(node).next().unwrap();
+        (1..=node.depth())
+            .rev()
+            .find(|&len| {
+                let end = start + len;
+                end == values.len() || sa.mode().is_index(end, &values[end])
+            })
+            .unwrap_or(0)
+    }
+
+    /// Values from the previous indexed position to the suffix of `leaf`, or [`None`] if it is the first.
+    fn leaf_context(&self, leaf: Node) -> Option<&'a [T]> {
+        let sa = self.suffix_array();
+        let values = sa.values().as_ref();
+        let pos = self.positions(leaf).next().unwrap();
+        (0..pos)
+            .rev()
+            .find(|&p| !sa.mode().need_check() || sa.mode().is_index(p, &values[p]))
+            .map(|p| &values[p..pos])
+    }
+}
+
+fn repeat<B, T, M, I>(esa: &EnhancedSuffixArray<'_, B, T, M, I>, node: Node, len: usize) -> Repeat
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    let mut positions = esa.positions(node).collect::<Vec<_>>();
+    positions.sort_unstable();
+    Repeat { len, positions }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::Repeat;
+    use crate::{esa::EnhancedSuffixArray, gens::StrIndex, SuffixArray};
+
+    fn occurrences(text: &[u8], s: &[u8]) -> Vec<usize> {
+        (0..text.len())
+            .filter(|&p| text[p..].starts_with(s))
+            .collect()
+    }
+
+    /// Returns true if some context is the end of the text or differs from the others.
+    fn varies(mut contexts: impl Iterator<Item = Option<u8>>) -> bool {
+        let first = contexts.next().unwrap();
+        first.is_none() || contexts.any(|c| c.is_none() || c != first)
+    }
+
+    fn naive_maximal(text: &[u8]) -> BTreeSet<Repeat> {
+        let mut repeats = BTreeSet::new();
+        for start in 0..text.len() {
+            for end in start + 1..=text.len() {
+                let s = &text[start..end];
+                let positions = occurrences(text, s);
+                if positions.len() < 2 {
+                    continue;
+                }
+                let len = s.len();
+                let left = positions.iter().map(|&p| p.checked_sub(1).map(|q| text[q]));
+                let right = positions.iter().map(|&p| text.get(p + len).copied());
+                if varies(left) && varies(right) {
+                    repeats.insert(Repeat { len, positions });
+                }
+            }
+        }
+        repeats
+    }
+
+    #[test]
+    fn test_repeats() {
+        for value in [
+            "",
+            "a",
+            "aaaa",
+            "abab",
+            "mississippi",
+            "abcabcabcabx",
+            "xabcyabcwabcyz",
+            "acgtacgtaacgtttgacgatacgacgtacgtagctagcatcg",
+        ] {
+            let text = value.as_bytes();
+            let sa = SuffixArray::<_, u8, ()>::new(value);
+            let expected = naive_maximal(text);
+            let maximal = sa.maximal_repeats(0);
+            assert_eq!(maximal.len(), expected.len(), "{value}");
+            assert_eq!(maximal.iter().cloned().collect::<BTreeSet<_>>(), expected);
+
+            let supermaximal = expected
+                .iter()
+                .filter(|r| {
+                    let s = r.slice(text);
+                    expected.iter().all(|o| {
+                        o.len() <= r.len() || !o.slice(text).windows(s.len()).any(|w| w == s)
+                    })
+                })
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            let found = sa.supermaximal_repeats(0);
+            assert_eq!(
+                found.iter().cloned().collect::<BTreeSet<_>>(),
+                supermaximal,
+                "{value}"
+            );
+
+            let longest = sa.longest_repeated_substring();
+            assert_eq!(
+                longest.as_ref().map(|r| r.len()),
+                expected.iter().map(|r| r.len()).max(),
+                "{value}"
+            );
+            assert!(sa.maximal_repeats(3).iter().all(|r| r.len() >= 3));
+
+            let esa = EnhancedSuffixArray::new(&sa);
+            assert_eq!(esa.maximal_repeats(0), maximal);
+            assert_eq!(esa.supermaximal_repeats(0), found);
+            assert_eq!(esa.longest_repeated_substring(), longest);
+        }
+    }
+
+    #[test]
+    fn test_repeats_str() {
+        let value = "錆さび錆さぶ_錆さ";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(value);
+        let longest = sa.longest_repeated_substring().unwrap();
+        assert_eq!(longest.slice(value.as_bytes()), "錆さ".as_bytes());
+        assert_eq!(longest.positions(), [0, 9, 19]);
+        for repeat in sa.maximal_repeats(0) {
+            let s = std::str::from_utf8(repeat.slice(value.as_bytes())).unwrap();
+            for &p in repeat.positions() {
+                assert!(value.is_char_boundary(p) && value[p..].starts_with(s));
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Suffix array keeping only sampled entries, to trade memory for locate speed.
+//!
+//! [`SampledSuffixArray`] keeps the indices of every `sample_rate`-th indexed text position and
+//! recovers the others by LF-mapping over the [`Bwt`](`crate::bwt::Bwt`) of the values,
+//! held in the wavelet matrix of an [`FmIndex`].
+//! The memory of the indices shrinks to about `1 / sample_rate`, plus `⌈log2 σ⌉` bits and the rank
+//! counts for each value, and each recovered entry costs LF steps back to the previous sample.
+
+use crate::{
+    fm::{FmIndex, RankBits, RankBitsBuilder},
+    gens::{builders::SAISBuilder, IndexMode},
+    indices::{IndexStorage, SaIndex},
+    search::MatchRange,
+    SuffixArray,
+};
+use bitvec::prelude::*;
+
+/// Suffix array keeping only the sampled indices.
+#[derive(Debug, Clone)]
+pub struct SampledSuffixArray<B, T = u8, M = ()> {
+    values: B,
+    mode: M,
+    locator: Locator<T>,
+}
+
+/// The [`FmIndex`] of every suffix and the ranks of the suffixes kept by the mode.
+#[derive(Debug, Clone)]
+struct Locator<T> {
+    fm: FmIndex<T>,
+    /// ranks of the kept suffixes among every suffix, `None` if every suffix is kept
+    indexed: Option<RankBits>,
+    len: usize,
+}
+
+impl<T: Ord + Clone> Locator<T> {
+    /// Text position of the kept suffix at `rank`.
+    #[inline]
+    fn locate(&self, rank: usize) -> usize {
+        assert!(rank < self.len, "rank {rank} is out of range");
+        match &self.indexed {
+            Some(indexed) => self.fm.locate_rank(indexed.select(rank)),
+            None => self.fm.locate_rank(rank),
+        }
+    }
+
+    /// Ranks of the kept suffixes in the ranks of every suffix.
+    #[inline]
+    fn search(&self, pattern: &[T]) -> MatchRange {
+        let range = self.fm.backward_search(pattern);
+        match &self.indexed {
+            Some(indexed) => {
+                MatchRange::new(indexed.rank(range.start()), indexed.rank(range.end()))
+            }
+            None => range,
+        }
+    }
+}
+
+impl<B, T, M> SampledSuffixArray<B, T, M>
+where
+    B: AsRef<[T]>,
+    T: Ord + Clone,
+    M: IndexMode<T>,
+{
+    /// Build from a [`SuffixArray`], keeping the indices of every `sample_rate`-th text position kept by the mode.
+    ///
+    /// If some index is filtered by the [`IndexMode`], every suffix is sorted again for the LF steps,
+    /// and a recovered entry walks the filtered positions between the samples too.
+    ///
+    /// # Panics
+    /// Panics if `sample_rate` is `0`.
+    pub fn new<I>(sa: SuffixArray<B, T, M, I>, sample_rate: usize) -> Self
+    where
+        I: IndexStorage,
+    {
+        assert_ne!(sample_rate, 0, "sample rate should be positive");
+        let len = sa.indices().len();
+        let (values, indices, mode) = sa.into_parts();
+        let text = values.as_ref();
+        let locator = if len == text.len() {
+            let fm = FmIndex::from_suffixes(
+                text,
+                indices.as_slice().iter().map(|i| i.to_usize()),
+                sample_rate,
+                |pos| pos % sample_rate == 0,
+            );
+            Locator {
+                fm,
+                indexed: None,
+                len,
+            }
+        } else {
+            drop(indices);
+            let full = SuffixArray::<_, T, ()>::new_by::<SAISBuilder>(text, ());
+            // every `sample_rate`-th kept position.
+            let mut sampled = bitvec![0; text.len()];
+            let kept = text
+                .iter()
+                .enumerate()
+                .filter(|(i, v)| mode.is_index(*i, v));
+            for (k, (i, _)) in kept.enumerate() {
+                if k % sample_rate == 0 {
+                    sampled.set(i, true);
+                }
+            }
+            let mut indexed = RankBitsBuilder::new(text.len());
+            for (rank, pos) in full.indices().iter().enumerate() {
+                if mode.is_index(*pos, &text[*pos]) {
+                    indexed.set(rank);
+                }
+            }
+            let fm =
+                FmIndex::from_suffixes(text, full.indices().iter().copied(), sample_rate, |pos| {
+                    sampled[pos]
+                });
+            Locator {
+                fm,
+                indexed: Some(indexed.build()),
+                len,
+            }
+        };
+        Self {
+            values,
+            mode,
+            locator,
+        }
+    }
+
+    /// Get a reference to the values.
+    #[inline]
+    pub fn values(&self) -> &B {
+        &self.values
+    }
+
+    /// Get a reference to the mode.
+    #[inline]
+    pub fn mode(&self) -> &M {
+        &self.mode
+    }
+
+    /// Kept text positions between the samples.
+    #[inline]
+    pub fn sample_rate(&self) -> usize {
+        self.locator.fm.sample_rate()
+    }
+
+    /// Number of the indices.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locator.len
+    }
+
+    /// Returns true if there is no index.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of the sampled indices and the structures to recover the others, without the values.
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() - std::mem::size_of::<FmIndex<T>>()
+            + self.locator.fm.size_in_bytes()
+            + self
+                .locator
+                .indexed
+                .as_ref()
+                .map_or(0, RankBits::size_in_bytes)
+    }
+
+    /// Text position of the suffix at `rank`, the same as `indices()[rank]` of the original array.
+    ///
+    /// # Panics
+    /// Panics if `rank` is out of range.
+    #[inline]
+    pub fn suffix_at(&self, rank: usize) -> usize {
+        self.locator.locate(rank)
+    }
+
+    /// Search `values` like [`SuffixArray::search_naive`].
+    ///
+    /// The matched range is found by backward search over the BWT.
+    /// If there is no match, the insertion rank is found by binary search recovering the probed entries.
+    pub fn search_naive<B2>(&self, values: B2) -> Result<MatchRange, usize>
+    where
+        B2: AsRef<[T]>,
+    {
+        let pattern = values.as_ref();
+        let range = self.locator.search(pattern);
+        if !range.is_empty() || pattern.is_empty() {
+            return Ok(range);
+        }
+        let text = self.values.as_ref();
+        let (mut l, mut r) = (0, self.len());
+        while l < r {
+            let mid = (l + r) / 2;
+            let p = self.suffix_at(mid);
+            if text[p..] < *pattern {
+                l = mid + 1;
+            } else {
+                r = mid;
+            }
+        }
+        Err(l)
+    }
+
+    /// Iterate the text positions where `pattern` occurs, like [`SuffixArray::find_iter`].
+    pub fn find_iter<B2>(&self, pattern: B2) -> SampledFindIter<'_, T>
+    where
+        B2: AsRef<[T]>,
+    {
+        SampledFindIter {
+            locator: &self.locator,
+            ranks: self.locator.search(pattern.as_ref()).ranks(),
+        }
+    }
+}
+
+/// Iterator of the occurrence positions of a pattern. Created by [`SampledSuffixArray::find_iter`].
+#[derive(Debug, Clone)]
+pub struct SampledFindIter<'a, T = u8> {
+    locator: &'a Locator<T>,
+    ranks: std::ops::Range<usize>,
+}
+
+impl<T: Ord + Clone> SampledFindIter<'_, T> {
+    /// Collect the remaining positions and iterate them in ascending text order.
+    pub fn in_text_order(self) -> std::vec::IntoIter<usize> {
+        let mut positions = self.collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions.into_iter()
+    }
+}
+
+impl<T: Ord + Clone> Iterator for SampledFindIter<'_, T> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        self.ranks.next().map(|rank| self.locator.locate(rank))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranks.size_hint()
+    }
+}
+
+impl<T: Ord + Clone> DoubleEndedIterator for SampledFindIter<'_, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<usize> {
+        self.ranks.next_back().map(|rank| self.locator.locate(rank))
+    }
+}
+
+impl<T: Ord + Clone> ExactSizeIterator for SampledFindIter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::SampledSuffixArray;
+    use crate::{gens::StrIndex, SuffixArray};
+
+    #[test]
+    fn test_sampled() {
+        let text = "mississippi_abracadabra_錆さび錆さびさび_aaaaaaaaaaabababab";
+        let sa = SuffixArray::<_, u8, ()>::new(text);
+        let expected = sa.indices().to_vec();
+        let sampled = SampledSuffixArray::new(sa.clone(), 5);
+        assert_eq!(sampled.len(), expected.len());
+        for (rank, p) in expected.iter().enumerate() {
+            assert_eq!(sampled.suffix_at(rank), *p);
+        }
+        for pattern in [
+            "", "i", "ss", "abra", "さび", "aaaa", "x", "pix", "zz", "\0",
+        ] {
+            assert_eq!(
+                sampled.search_naive(pattern),
+                sa.search_naive(pattern),
+                "{pattern}"
+            );
+            assert_eq!(
+                sampled
+                    .find_iter(pattern)
+                    .in_text_order()
+                    .collect::<Vec<_>>(),
+                sa.find_iter(pattern).in_text_order().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sampled_filtered() {
+        let text = "錆さび_abc錆_さびさび錆錆";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        for rate in [1, 2, 3, 100] {
+            let sampled = SampledSuffixArray::new(sa.clone(), rate);
+            assert_eq!(sampled.len(), sa.indices().len());
+            for (rank, p) in sa.indices().iter().enumerate() {
+                assert_eq!(sampled.suffix_at(rank), *p);
+            }
+            for pattern in ["", "錆", "さび", "_", "abc錆", "\u{88}", "x"] {
+                assert_eq!(
+                    sampled.search_naive(pattern),
+                    sa.search_naive(pattern),
+                    "{pattern}"
+                );
+                assert!(sampled.find_iter(pattern).eq(sa.find_iter(pattern)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sampled_values() {
+        let values = [5u32, 1 << 20, 5, 3, 1 << 20, 5, 3, 0, 5];
+        let sa = SuffixArray::<_, u32, ()>::new(&values[..]);
+        let sampled = SampledSuffixArray::new(sa.clone(), 2);
+        for (rank, p) in sa.indices().iter().enumerate() {
+            assert_eq!(sampled.suffix_at(rank), *p);
+        }
+        for pattern in [&[5, 3][..], &[1 << 20], &[4], &[5, 1 << 20, 5]] {
+            assert_eq!(sampled.search_naive(pattern), sa.search_naive(pattern));
+        }
+    }
+
+    #[test]
+    fn test_sampled_memory() {
+        let mut state = 1u32;
+        let text = (0..1 << 16)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let sa = SuffixArray::<_, u8, ()>::new(&text[..]);
+        let full = std::mem::size_of_val(sa.indices());
+        let sampled = SampledSuffixArray::new(sa, 16);
+        // 8 bits for each value, the rank counts and the samples are under a quarter of the indices.
+        assert!(
+            sampled.size_in_bytes() < full / 4,
+            "{} bytes for the indices of {full} bytes",
+            sampled.size_in_bytes()
+        );
+    }
+}
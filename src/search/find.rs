@@ -0,0 +1,107 @@
+use super::{MatchRange, Positions};
+use crate::{
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    /// Iterate the text positions where `pattern` occurs, in suffix order.
+    ///
+    /// Only the positions kept by the [`IndexMode`](`crate::gens::modes::IndexMode`) used to build
+    /// this array are yielded, so arrays built with [`StrIndex`](`crate::gens::modes::StrIndex`)
+    /// yield only char boundaries.
+    /// Use [`in_text_order`](`FindIter::in_text_order`) to get them in ascending text order.
+    pub fn find_iter<B2>(&self, pattern: B2) -> FindIter<'_, I::Index>
+    where
+        B2: AsRef<[T]>,
+    {
+        let range = self.search_naive(pattern).unwrap_or_default();
+        FindIter {
+            range,
+            positions: range.positions(self),
+        }
+    }
+}
+
+/// Iterator of the occurrence positions of a pattern. Created by [`SuffixArray::find_iter`].
+#[derive(Debug, Clone)]
+pub struct FindIter<'a, X = usize> {
+    range: MatchRange,
+    positions: Positions<'a, X>,
+}
+
+impl<X: SaIndex> FindIter<'_, X> {
+    /// The [`MatchRange`] which this iterator is created from.
+    #[inline]
+    pub fn range(&self) -> MatchRange {
+        self.range
+    }
+
+    /// Collect the remaining positions and iterate them in ascending text order.
+    pub fn in_text_order(self) -> std::vec::IntoIter<usize> {
+        let mut positions = self.positions.collect::<Vec<_>>();
+        positions.sort_unstable();
+        positions.into_iter()
+    }
+}
+
+impl<X: SaIndex> Iterator for FindIter<'_, X> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        self.positions.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.positions.size_hint()
+    }
+}
+
+impl<X: SaIndex> DoubleEndedIterator for FindIter<'_, X> {
+    #[inline]
+    fn next_back(&mut self) -> Option<usize> {
+        self.positions.next_back()
+    }
+}
+
+impl<X: SaIndex> ExactSizeIterator for FindIter<'_, X> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{gens::StrIndex, SuffixArray};
+
+    #[test]
+    fn test_find_iter() {
+        let sa = SuffixArray::<_, u8, ()>::new("abcabcab");
+        assert_eq!(sa.find_iter("ab").len(), 3);
+        assert_eq!(
+            sa.find_iter("ab").in_text_order().collect::<Vec<_>>(),
+            [0, 3, 6]
+        );
+        assert_eq!(
+            sa.find_iter("bc").in_text_order().collect::<Vec<_>>(),
+            [1, 4]
+        );
+        assert_eq!(sa.find_iter("ac").next(), None);
+    }
+
+    #[test]
+    fn test_find_iter_str() {
+        let text = "錆さび錆さびさび";
+        let sa = SuffixArray::<_, u8, StrIndex>::new(text);
+        let found = sa.find_iter("さび").in_text_order().collect::<Vec<_>>();
+        assert_eq!(found, [3, 12, 18]);
+        assert!(found.iter().all(|p| text.is_char_boundary(*p)));
+        // the last byte of "び" is not a char boundary
+        assert_eq!(sa.find_iter([0xb3]).count(), 0);
+        let sa = SuffixArray::<_, u8, ()>::new(text);
+        assert_eq!(sa.find_iter([0xb3]).count(), 3);
+    }
+}
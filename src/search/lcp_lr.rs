@@ -0,0 +1,223 @@
+//! Binary search skipping the already matched prefix of the pattern.
+//!
+//! [`MlrSearcher`] uses the Manber–Myers mlr trick, starting each comparison after
+//! `min(l, r)` values, where `l` and `r` are the lcp of the pattern with the bounds.
+//! [`LcpLr`] also keeps the lcp of every bound with its middle, so the search is
+//! `O(m + log n)` for a pattern of length `m`.
+
+use std::cmp::Ordering;
+
+use crate::{
+    gens::IndexMode,
+    indices::{IndexStorage, SaIndex},
+    lcp::LcpArray,
+    SuffixArray,
+};
+
+use super::{MatchRange, Searcher};
+
+/// [`Searcher`] by binary search with the Manber–Myers mlr trick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MlrSearcher;
+
+impl<B: AsRef<[T]>, T: Ord, M, B2: AsRef<[T]>> Searcher<B, T, M, B2> for MlrSearcher {
+    fn search_range<I: IndexStorage>(
+        sa: &SuffixArray<B, T, M, I>,
+        target: B2,
+    ) -> Result<MatchRange, usize> {
+        search_by_bounds(sa, target.as_ref(), None)
+    }
+}
+
+/// Precomputed LCP-LR of a [`SuffixArray`] for `O(m + log n)` search.
+///
+/// For each rank `mid` visited by the binary search between the bounds `l` and `r`,
+/// it keeps the lcp of the suffixes at `l` and `mid`, and at `mid` and `r`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct LcpLr {
+    llcp: Vec<usize>,
+    rlcp: Vec<usize>,
+}
+
+impl LcpLr {
+    /// Build the LCP-LR of `sa`.
+    pub fn new<B, T, M, I>(sa: &SuffixArray<B, T, M, I>) -> Self
+    where
+        T: Ord,
+        B: AsRef<[T]>,
+        M: IndexMode<T>,
+        I: IndexStorage,
+    {
+        Self::from_lcp(&sa.lcp())
+    }
+
+    /// Build from the [`LcpArray`] of the suffix array to search.
+    pub fn from_lcp(lcp: &LcpArray) -> Self {
+        let n = lcp.len();
+        let mut lcp_lr = Self {
+            llcp: vec![0; n],
+            rlcp: vec![0; n],
+        };
+        // bounds are shifted by one, so that `0` and `n + 1` are the virtual suffixes out of the array.
+        fn fill(lcp_lr: &mut LcpLr, lcp: &[usize], l: usize, r: usize) -> usize {
+            if r - l == 1 {
+                // lcp between the ranks `l - 1` and `r - 1`, 0 if one of them is virtual.
+                return if l == 0 || r > lcp.len() {
+                    0
+                } else {
+                    lcp[r - 1]
+                };
+            }
+            let mid = (l + r) / 2;
+            let left = fill(lcp_lr, lcp, l, mid);
+            let right = fill(lcp_lr, lcp, mid, r);
+            lcp_lr.llcp[mid - 1] = left;
+            lcp_lr.rlcp[mid - 1] = right;
+            left.min(right)
+        }
+        fill(&mut lcp_lr, lcp, 0, n + 1);
+        lcp_lr
+    }
+
+    /// Search `target` in `sa`, which should be the array this LCP-LR is built from.
+    ///
+    /// Returns the same as [`SuffixArray::search_naive`].
+    pub fn search<B, T, M, I, B2>(
+        &self,
+        sa: &SuffixArray<B, T, M, I>,
+        target: B2,
+    ) -> Result<MatchRange, usize>
+    where
+        T: Ord,
+        B: AsRef<[T]>,
+        I: IndexStorage,
+        B2: AsRef<[T]>,
+    {
+        debug_assert_eq!(self.llcp.len(), sa.indices().len());
+        search_by_bounds(sa, target.as_ref(), Some(self))
+    }
+}
+
+fn search_by_bounds<B, T, M, I>(
+    sa: &SuffixArray<B, T, M, I>,
+    target: &[T],
+    lcp_lr: Option<&LcpLr>,
+) -> Result<MatchRange, usize>
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    let start = bound(sa, target, lcp_lr, false);
+    let end = bound(sa, target, lcp_lr, true);
+    if start < end {
+        Ok(MatchRange::new(start, end))
+    } else {
+        Err(start)
+    }
+}
+
+/// First rank whose suffix is after `target`, comparing the first `target.len()` values.
+/// If `upper` is false, a suffix starting with `target` is also after it.
+fn bound<B, T, M, I>(
+    sa: &SuffixArray<B, T, M, I>,
+    target: &[T],
+    lcp_lr: Option<&LcpLr>,
+    upper: bool,
+) -> usize
+where
+    T: Ord,
+    B: AsRef<[T]>,
+    I: IndexStorage,
+{
+    let values = sa.values().as_ref();
+    let indices = sa.indices();
+    // Returns whether the suffix at `rank` is before the bound, and its lcp with `target`,
+    // knowing the first `k` values are same.
+    let compare = |rank: usize, k: usize| {
+        let suffix = &values[indices[rank].to_usize()..];
+        let k = k + target[k..]
+            .iter()
+            .zip(&suffix[k..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        let before = if k == target.len() {
+            upper
+        } else if k == suffix.len() {
+            true
+        } else {
+            suffix[k] < target[k]
+        };
+        (before, k)
+    };
+    // bounds are shifted by one as in `LcpLr::from_lcp`.
+    let (mut l, mut r) = (0, indices.len() + 1);
+    let (mut l_lcp, mut r_lcp) = (0, 0);
+    while r - l > 1 {
+        let mid = (l + r) / 2;
+        let (before, k) = match lcp_lr {
+            Some(lcp_lr) if l_lcp >= r_lcp => match lcp_lr.llcp[mid - 1].cmp(&l_lcp) {
+                Ordering::Greater => (true, l_lcp),
+                Ordering::Less => (false, lcp_lr.llcp[mid - 1]),
+                Ordering::Equal => compare(mid - 1, l_lcp),
+            },
+            Some(lcp_lr) => match lcp_lr.rlcp[mid - 1].cmp(&r_lcp) {
+                Ordering::Greater => (false, r_lcp),
+                Ordering::Less => (true, lcp_lr.rlcp[mid - 1]),
+                Ordering::Equal => compare(mid - 1, r_lcp),
+            },
+            None => compare(mid - 1, l_lcp.min(r_lcp)),
+        };
+        if before {
+            l = mid;
+            l_lcp = k;
+        } else {
+            r = mid;
+            r_lcp = k;
+        }
+    }
+    l
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LcpLr, MlrSearcher};
+    use crate::{gens::StrIndex, SuffixArray};
+
+    #[test]
+    fn test_lcp_lr() {
+        let text =
+            "acgtacgtaacgtttgacgatacgacgtacgtagctagcatcgatcgaaacgacgtacgtacgacgacgtacgac錆さび錆";
+        let patterns = [
+            "",
+            "a",
+            "acg",
+            "acgt",
+            "acgtacgt",
+            "acgacgtacgtacgac",
+            "ga",
+            "gag",
+            "t",
+            "tttt",
+            "z",
+            "錆",
+            "さ",
+            "\0",
+        ];
+        for end in [0, 1, 2, 5, 16, text.len()] {
+            let value = &text[..end];
+            let sa = SuffixArray::<_, u8, ()>::new(value);
+            let lcp_lr = LcpLr::new(&sa);
+            let filtered = SuffixArray::<_, u8, StrIndex>::new(value);
+            let filtered_lcp_lr = LcpLr::new(&filtered);
+            for pattern in patterns {
+                let expected = sa.search_naive(pattern);
+                assert_eq!(lcp_lr.search(&sa, pattern), expected, "{value} {pattern}");
+                assert_eq!(sa.search::<MlrSearcher, _>(pattern), expected);
+                let expected = filtered.search_naive(pattern);
+                assert_eq!(filtered_lcp_lr.search(&filtered, pattern), expected);
+                assert_eq!(filtered.search::<MlrSearcher, _>(pattern), expected);
+            }
+        }
+    }
+}
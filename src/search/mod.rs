@@ -1,20 +1,36 @@
-use std::cmp::Ordering;
+//! Searching a pattern in a built [`SuffixArray`].
+//!
+//! A search returns a [`MatchRange`], the ranks in [`indices`](`SuffixArray::indices`)
+//! whose suffixes start with the pattern.
 
-use crate::SuffixArray;
+use std::{cmp::Ordering, ops::Range};
 
-impl<T, B, M> SuffixArray<B, T, M>
+use crate::{
+    indices::{IndexStorage, SaIndex},
+    SuffixArray,
+};
+
+mod find;
+mod lcp_lr;
+pub use find::*;
+pub use lcp_lr::*;
+
+impl<T, B, M, I> SuffixArray<B, T, M, I>
 where
     T: Ord,
     B: AsRef<[T]>,
+    I: IndexStorage,
 {
-    pub fn search<S: Searcher<B, T, M, B2>, B2>(
-        &self,
-        values: B2,
-    ) -> Result<(usize, usize), usize> {
+    /// Search `values` by the [`Searcher`] `S`.
+    ///
+    /// Returns the [`MatchRange`] of the matched suffixes,
+    /// or the rank where `values` would be inserted if there is no match.
+    pub fn search<S: Searcher<B, T, M, B2>, B2>(&self, values: B2) -> Result<MatchRange, usize> {
         S::search_range(self, values)
     }
 
-    pub fn search_naive<B2>(&self, values: B2) -> Result<(usize, usize), usize>
+    /// Search `values` by [`NaiveSearcher`].
+    pub fn search_naive<B2>(&self, values: B2) -> Result<MatchRange, usize>
     where
         B2: AsRef<[T]>,
     {
@@ -22,38 +38,156 @@ where
     }
 }
 
+/// Range of ranks in [`SuffixArray::indices`] matched by a search.
+///
+/// `start..end` are ranks, not text positions. Use [`positions`](`MatchRange::positions`)
+/// to get where the matches are in the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct MatchRange {
+    start: usize,
+    end: usize,
+}
+
+impl MatchRange {
+    /// Create a new [`MatchRange`] of the ranks `start..end`.
+    #[inline]
+    pub fn new(start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        Self { start, end }
+    }
+
+    /// The first matched rank.
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The rank after the last matched rank.
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Number of the matched suffixes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns true if no suffix is matched.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The matched ranks as a [`Range`].
+    #[inline]
+    pub fn ranks(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Text positions of the matched suffixes, in suffix order.
+    ///
+    /// `sa` should be the [`SuffixArray`] which this range is searched from.
+    #[inline]
+    pub fn positions<'a, B, T, M, I>(
+        &self,
+        sa: &'a SuffixArray<B, T, M, I>,
+    ) -> Positions<'a, I::Index>
+    where
+        T: Ord,
+        B: AsRef<[T]>,
+        I: IndexStorage,
+    {
+        Positions {
+            inner: sa.indices()[self.ranks()].iter(),
+        }
+    }
+}
+
+impl From<MatchRange> for Range<usize> {
+    #[inline]
+    fn from(range: MatchRange) -> Self {
+        range.ranks()
+    }
+}
+
+/// Iterator of the text positions covered by a [`MatchRange`].
+/// Created by [`MatchRange::positions`].
+#[derive(Debug, Clone)]
+pub struct Positions<'a, X = usize> {
+    inner: std::slice::Iter<'a, X>,
+}
+
+impl<X: SaIndex> Iterator for Positions<'_, X> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        self.inner.next().map(|i| i.to_usize())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<X: SaIndex> DoubleEndedIterator for Positions<'_, X> {
+    #[inline]
+    fn next_back(&mut self) -> Option<usize> {
+        self.inner.next_back().map(|i| i.to_usize())
+    }
+}
+
+impl<X: SaIndex> ExactSizeIterator for Positions<'_, X> {}
+
+/// Algorithm to search a pattern `B2` in a [`SuffixArray`].
 pub trait Searcher<B, T, M, B2> {
-    fn search_range(sa: &SuffixArray<B, T, M>, target: B2) -> Result<(usize, usize), usize>;
-    fn search_contains(sa: &SuffixArray<B, T, M>, target: B2) -> bool {
+    /// Search the ranks of the suffixes starting with `target`.
+    ///
+    /// Returns the rank where `target` would be inserted if there is no match.
+    fn search_range<I: IndexStorage>(
+        sa: &SuffixArray<B, T, M, I>,
+        target: B2,
+    ) -> Result<MatchRange, usize>;
+    /// Returns true if some suffix starts with `target`.
+    fn search_contains<I: IndexStorage>(sa: &SuffixArray<B, T, M, I>, target: B2) -> bool {
         Self::search_range(sa, target).is_ok()
     }
 }
 
+/// [`Searcher`] by binary search comparing the pattern with each suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct NaiveSearcher;
 
 impl<B: AsRef<[T]>, T: Ord, M, B2: AsRef<[T]>> Searcher<B, T, M, B2> for NaiveSearcher {
-    fn search_range(sa: &SuffixArray<B, T, M>, target: B2) -> Result<(usize, usize), usize> {
+    fn search_range<I: IndexStorage>(
+        sa: &SuffixArray<B, T, M, I>,
+        target: B2,
+    ) -> Result<MatchRange, usize> {
         let t = target.as_ref();
         let tlen = t.len();
         let vals = sa.values().as_ref();
         let len = vals.len();
         binary_search_range_by(sa.indices(), |i| {
-            let i = *i;
+            let i = i.to_usize();
             if i + tlen < len {
                 t.cmp(&vals[i..i + tlen])
             } else {
                 t.cmp(&vals[i..])
             }
         })
+        .map(|(l, r)| MatchRange::new(l, r))
     }
 
-    fn search_contains(sa: &SuffixArray<B, T, M>, target: B2) -> bool {
+    fn search_contains<I: IndexStorage>(sa: &SuffixArray<B, T, M, I>, target: B2) -> bool {
         let t = target.as_ref();
         let tlen = t.len();
         let vals = sa.values().as_ref();
         let len = vals.len();
         binary_first_match(sa.indices(), |i| {
-            let i = *i;
+            let i = i.to_usize();
             if i + tlen < len {
                 t.cmp(&vals[i..i + tlen])
             } else {
@@ -132,4 +266,17 @@ mod tests {
         assert_eq!(super::binary_search_range_by(&x, |x| 11.cmp(x)), Err(8));
         assert_eq!(super::binary_search_range_by(&x, |x| 12.cmp(x)), Ok((8, 11)));
     }
+
+    #[test]
+    fn test_match_range() {
+        use crate::gens::builders::NaiveBuilder;
+        let sa = crate::SuffixArray::<_, u8, _>::new_by::<NaiveBuilder>("abcabcab", ());
+        let range = sa.search_naive("ab").unwrap();
+        assert_eq!(range.len(), 3);
+        let mut positions = range.positions(&sa).collect::<Vec<_>>();
+        positions.sort_unstable();
+        assert_eq!(positions, [0, 3, 6]);
+        assert_eq!(sa.search_naive("ac"), Err(range.end()));
+        assert!(sa.search_naive("").unwrap().len() == 8);
+    }
 }
@@ -0,0 +1,120 @@
+//! The [`PeakMemory`] estimates bound the memory measured by a counting allocator.
+//!
+//! The allocator counts every thread, so this file has only one test,
+//! and each build is measured a few times.
+
+use bd_suffix::{
+    gens::builders::*,
+    indices::{SaIndex, U40},
+    SuffixArray,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Peak bytes allocated by `f` over the allocated ones before it.
+fn measure<R>(f: impl FnOnce() -> R) -> usize {
+    let base = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(base, Ordering::SeqCst);
+    let r = f();
+    let peak = PEAK.load(Ordering::SeqCst) - base;
+    drop(r);
+    peak
+}
+
+fn check<B, T, X>(name: &str, values: &[T])
+where
+    B: Builder<&'static [T], T, ()> + PeakMemory,
+    T: Ord + Clone + 'static,
+    X: SaIndex,
+{
+    // the values outlive the test.
+    let values: &'static [T] = Box::leak(values.to_vec().into_boxed_slice());
+    // the harness may allocate on another thread meanwhile, so take the least of a few builds.
+    let measured = (0..3)
+        .map(|_| measure(|| SuffixArray::<_, T, (), Vec<X>>::try_new_by_width::<B>(values, ())))
+        .min()
+        .unwrap();
+    let estimate = B::peak_memory_width(values.len(), X::WIDTH);
+    assert!(
+        measured <= estimate,
+        "{name} of {} entries of {} bytes: measured {measured} > estimate {estimate}",
+        values.len(),
+        X::WIDTH
+    );
+}
+
+fn check_widths<B, T>(name: &str, values: &[T])
+where
+    B: Builder<&'static [T], T, ()> + PeakMemory,
+    T: Ord + Clone + 'static,
+{
+    check::<B, T, usize>(name, values);
+    check::<B, T, u32>(name, values);
+    check::<B, T, U40>(name, values);
+}
+
+fn lcg(len: usize, sigma: u32) -> Vec<u32> {
+    let mut state = 7u64;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % sigma as u64) as u32
+        })
+        .collect()
+}
+
+#[test]
+fn peak_memory_bounds_measured() {
+    #[cfg(feature = "parallel")]
+    // start the thread pool before measuring.
+    SuffixArray::<_, u8, ()>::new_by::<ParallelBuilder>(&b"warm up"[..], ());
+
+    let len = 10000;
+    let random = lcg(len, 4).into_iter().map(|v| v as u8).collect::<Vec<_>>();
+    let wide = lcg(len, 256)
+        .into_iter()
+        .map(|v| v as u8)
+        .collect::<Vec<_>>();
+    let periodic = b"abcab".repeat(len / 5);
+    let distinct = lcg(len, u32::MAX);
+    for text in [&random, &wide, &periodic] {
+        check_widths::<NaiveBuilder, u8>("naive", text);
+        check_widths::<BucketBuilder, u8>("bucket", text);
+        check_widths::<TwoStageBuilder, u8>("two stage", text);
+        check_widths::<TwoStageBuilderU8, u8>("two stage u8", text);
+        check_widths::<SAISBuilder, u8>("sais", text);
+        check_widths::<SAISBuilderU8, u8>("sais u8", text);
+        #[cfg(feature = "parallel")]
+        check_widths::<ParallelBuilder, u8>("parallel", text);
+    }
+    check_widths::<BucketBuilder, u32>("bucket", &distinct);
+    check_widths::<TwoStageBuilder, u32>("two stage", &distinct);
+    check_widths::<SAISBuilder, u32>("sais", &distinct);
+    #[cfg(feature = "parallel")]
+    check_widths::<ParallelBuilder, u32>("parallel", &distinct);
+}